@@ -0,0 +1,134 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ml_rust::{
+    ml::facial_processing::{anchor_center_array, anchor_centers, decode_anchors},
+    models::DetectedFaceOutput,
+};
+use ndarray::{Array2, ArrayViewD};
+
+const SIZE: u32 = 640;
+const STRIDES: [usize; 3] = [8, 16, 32];
+const NUM_ANCHORS: usize = 2;
+const THRESHOLD: f32 = 0.5;
+
+/// Выходы SCRFD одного шага сетки: оценки, рамки и ключевые точки.
+struct Level {
+    stride: usize,
+    scores: Array2<f32>,
+    bboxes: Array2<f32>,
+    kpsses: Array2<f32>,
+}
+
+/// Псевдослучайные выходы, в которых доля `positive` якорей выше порога.
+fn levels(positive: f32) -> Vec<Level> {
+    let mut state: u64 = 7;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) as f32 / (1u64 << 31) as f32
+    };
+
+    STRIDES
+        .iter()
+        .map(|&stride| {
+            let count = anchor_centers(SIZE, SIZE, stride, NUM_ANCHORS).len();
+            let score = |next: &mut dyn FnMut() -> f32| match next() < positive {
+                true => 0.5 + next() / 2.,
+                false => next() * THRESHOLD,
+            };
+
+            Level {
+                stride,
+                scores: Array2::from_shape_simple_fn((count, 1), || score(&mut next)),
+                bboxes: Array2::from_shape_simple_fn((count, 4), || next() * 4.),
+                kpsses: Array2::from_shape_simple_fn((count, 10), || next() * 4. - 2.),
+            }
+        })
+        .collect()
+}
+
+/// Прежнее декодирование: центры якорей на каждый вызов и поэлементный обход всех якорей.
+fn decode_scalar(levels: &[Level]) -> Vec<DetectedFaceOutput> {
+    let mut faces = vec![];
+
+    for level in levels {
+        let centers = anchor_centers(SIZE, SIZE, level.stride, NUM_ANCHORS);
+        let (scores, bboxes, kpsses): (ArrayViewD<f32>, ArrayViewD<f32>, ArrayViewD<f32>) = (
+            level.scores.view().into_dyn(),
+            level.bboxes.view().into_dyn(),
+            level.kpsses.view().into_dyn(),
+        );
+        let stride = level.stride as f32;
+
+        for (index, &(x, y)) in centers.iter().enumerate() {
+            let score = scores[[index, 0]];
+            if score > THRESHOLD {
+                faces.push(DetectedFaceOutput::new(
+                    score,
+                    [
+                        x - bboxes[[index, 0]] * stride,
+                        y - bboxes[[index, 1]] * stride,
+                        x + bboxes[[index, 2]] * stride,
+                        y + bboxes[[index, 3]] * stride,
+                    ],
+                    Some(std::array::from_fn(|point| {
+                        (
+                            x + kpsses[[index, point * 2]] * stride,
+                            y + kpsses[[index, point * 2 + 1]] * stride,
+                        )
+                    })),
+                ));
+            }
+        }
+    }
+
+    faces
+}
+
+fn decode_vectorized(levels: &[Level], centers: &[Array2<f32>]) -> Vec<DetectedFaceOutput> {
+    levels
+        .iter()
+        .zip(centers)
+        .flat_map(|(level, centers)| {
+            decode_anchors(
+                centers.view(),
+                level.stride,
+                level.scores.view(),
+                level.bboxes.view(),
+                Some(level.kpsses.view()),
+                THRESHOLD,
+            )
+        })
+        .collect()
+}
+
+fn anchor_decoding(criterion: &mut Criterion) {
+    let centers: Vec<Array2<f32>> = STRIDES
+        .iter()
+        .map(|&stride| anchor_center_array(SIZE, SIZE, stride, NUM_ANCHORS))
+        .collect();
+
+    let mut group = criterion.benchmark_group("anchor_decoding");
+    for positive in [0.001, 0.01, 0.1] {
+        let levels = levels(positive);
+        assert_eq!(
+            decode_scalar(&levels).len(),
+            decode_vectorized(&levels, &centers).len()
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("scalar", positive),
+            &levels,
+            |bench, levels| bench.iter(|| decode_scalar(black_box(levels))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("vectorized", positive),
+            &levels,
+            |bench, levels| bench.iter(|| decode_vectorized(black_box(levels), &centers)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, anchor_decoding);
+criterion_main!(benches);
@@ -1,27 +1,516 @@
-use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug, Deserialize, Clone)]
+use serde::{de, Deserialize, Deserializer};
+use utoipa::ToSchema;
+
+use crate::error::Error;
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct ModelData {
     pub model_path: String,
     pub model_name: String,
+    #[serde(default = "default_pool_size")]
+    pub pool_size: usize,
+    /// Максимальное количество элементов в одном запуске модели.
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+    #[serde(default)]
+    pub session: SessionOptions,
+    /// Динамическое объединение параллельных запросов в батчи. По умолчанию выключено.
+    pub batching: Option<BatchingOptions>,
+    /// Размер входа и сетка якорей (только для модели детекции).
+    #[serde(default)]
+    pub grid: GridOptions,
+    /// Формат выходов детектора (только для модели детекции).
+    #[serde(default)]
+    pub head: HeadOptions,
+    /// Детекция по перекрывающимся фрагментам в исходном разрешении
+    /// (только для модели детекции). По умолчанию выключена.
+    pub tiling: Option<TilingOptions>,
+    /// Масштабы и отражение для аугментации при детекции (только для модели детекции).
+    #[serde(default)]
+    pub tta: TtaOptions,
+    /// Параметры постобработки по умолчанию (только для модели детекции).
+    #[serde(default)]
+    pub detection: DetectionOptions,
+    /// Путь к локальному файлу `tokenizer.json` (только для текстовой модели).
+    pub tokenizer_path: Option<String>,
+    /// Разрешает загрузку токенизатора из Hugging Face Hub по `model_name`,
+    /// если `tokenizer_path` не указан или файл не удалось загрузить.
+    #[serde(default)]
+    pub tokenizer_from_hub: bool,
+    /// Ожидаемый SHA-256 файла модели. Если указан, проверяется при загрузке.
+    pub sha256: Option<String>,
+    /// Модель по умолчанию среди нескольких именованных моделей задачи.
+    #[serde(default)]
+    pub default: bool,
+}
+
+impl ModelData {
+    pub fn new(model_path: String, model_name: String) -> Self {
+        ModelData {
+            model_path,
+            model_name,
+            pool_size: default_pool_size(),
+            max_batch_size: default_max_batch_size(),
+            session: SessionOptions::default(),
+            batching: None,
+            grid: GridOptions::default(),
+            head: HeadOptions::default(),
+            tiling: None,
+            tta: TtaOptions::default(),
+            detection: DetectionOptions::default(),
+            tokenizer_path: None,
+            tokenizer_from_hub: false,
+            sha256: None,
+            default: false,
+        }
+    }
+
+    /// Совпадают ли параметры загрузки моделей без учета отметки `default`.
+    pub fn same_model(&self, other: &ModelData) -> bool {
+        ModelData {
+            default: other.default,
+            ..self.clone()
+        } == *other
+    }
+}
+
+fn default_pool_size() -> usize {
+    1
+}
+
+fn default_max_batch_size() -> usize {
+    32
+}
+
+/// Параметры сессии ONNX Runtime. Не указанные параметры остаются
+/// значениями ONNX Runtime по умолчанию.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct SessionOptions {
+    #[serde(default)]
+    pub optimization_level: OptimizationLevel,
+    pub intra_threads: Option<usize>,
+    pub inter_threads: Option<usize>,
+    pub parallel_execution: Option<bool>,
+    pub memory_arena: Option<bool>,
+    pub memory_pattern: Option<bool>,
+    /// Путь, по которому сохраняется оптимизированная модель.
+    pub optimized_model_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct BatchingOptions {
+    /// Максимальное количество элементов в одном батче.
+    pub max_batch_size: usize,
+    /// Максимальное время ожидания заполнения батча в миллисекундах.
+    pub max_wait_ms: u64,
+}
+
+/// Размер входа детектора и сетка якорей.
+///
+/// Изображение вписывается во вход с сохранением пропорций: в левый верхний угол
+/// или, если указано `center_padding`, по центру.
+///
+/// Для каждого шага `stride` центры якорей расположены в узлах сетки
+/// `(width / stride) x (height / stride)`, по `num_anchors` якорей в узле.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct GridOptions {
+    pub input_size: InputSize,
+    pub strides: Vec<usize>,
+    pub num_anchors: usize,
+    pub center_padding: bool,
+}
+
+impl Default for GridOptions {
+    fn default() -> Self {
+        GridOptions {
+            input_size: InputSize::Square(640),
+            strides: vec![8, 16, 32],
+            num_anchors: 2,
+            center_padding: false,
+        }
+    }
+}
+
+impl GridOptions {
+    /// Возвращает описания недопустимых значений параметров.
+    pub fn problems(&self) -> Vec<String> {
+        let (width, height) = self.input_size.dimensions();
+        let mut problems = vec![];
+
+        if width == 0 || height == 0 {
+            problems.push(format!("input_size must be positive, got {width}x{height}"));
+        }
+        if self.strides.is_empty() {
+            problems.push("strides must not be empty".to_string());
+        }
+        if self.num_anchors == 0 {
+            problems.push("num_anchors must be positive".to_string());
+        }
+        for stride in &self.strides {
+            if *stride == 0
+                || !(width as usize).is_multiple_of(*stride)
+                || !(height as usize).is_multiple_of(*stride)
+            {
+                problems.push(format!(
+                    "input_size {width}x{height} is not divisible by stride {stride}"
+                ));
+            }
+        }
+
+        problems
+    }
+}
+
+/// Формат выходов детектора.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum HeadOptions {
+    /// SCRFD: оценки `[N, 1]`, рамки `[N, 4]` и, если `keypoints`, ключевые точки `[N, 10]`
+    /// для каждого шага сетки. Без `outputs` выходы берутся по порядку:
+    /// сначала оценки всех шагов, затем рамки, затем ключевые точки.
+    Scrfd {
+        #[serde(default = "default_keypoints")]
+        keypoints: bool,
+        outputs: Option<ScrfdOutputs>,
+    },
+    /// YOLOv8-face без якорей: один выход `[4 + 1 (+ 15), N]` с центром и размером рамки,
+    /// оценкой и, если `keypoints`, ключевыми точками `(x, y, видимость)`.
+    /// Без `output` используется первый выход модели.
+    Yolov8 {
+        #[serde(default = "default_keypoints")]
+        keypoints: bool,
+        output: Option<String>,
+    },
+}
+
+impl Default for HeadOptions {
+    fn default() -> Self {
+        HeadOptions::Scrfd {
+            keypoints: default_keypoints(),
+            outputs: None,
+        }
+    }
+}
+
+fn default_keypoints() -> bool {
+    true
+}
+
+/// Имена выходов SCRFD, по одному на каждый шаг сетки в порядке `strides`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct ScrfdOutputs {
+    pub scores: Vec<String>,
+    pub bboxes: Vec<String>,
+    #[serde(default)]
+    pub keypoints: Vec<String>,
+}
+
+/// Размер входа: одно число для квадратного входа или `[ширина, высота]`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum InputSize {
+    Square(u32),
+    Rect([u32; 2]),
+}
+
+impl InputSize {
+    /// Ширина и высота входа.
+    pub fn dimensions(&self) -> (u32, u32) {
+        match *self {
+            InputSize::Square(size) => (size, size),
+            InputSize::Rect([width, height]) => (width, height),
+        }
+    }
+}
+
+/// Детекция по фрагментам: изображение, большее фрагмента, дополнительно
+/// обрабатывается перекрывающимися фрагментами `tile_size`x`tile_size` без уменьшения.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TilingOptions {
+    /// Сторона фрагмента в пикселях исходного изображения.
+    pub tile_size: u32,
+    /// Перекрытие соседних фрагментов в пикселях.
+    pub overlap: u32,
+}
+
+impl Default for TilingOptions {
+    fn default() -> Self {
+        TilingOptions {
+            tile_size: 640,
+            overlap: 128,
+        }
+    }
+}
+
+impl TilingOptions {
+    /// Возвращает описания недопустимых значений параметров.
+    pub fn problems(&self) -> Vec<String> {
+        match self.overlap < self.tile_size {
+            true => vec![],
+            false => vec![format!(
+                "tiling overlap {} must be less than tile_size {}",
+                self.overlap, self.tile_size
+            )],
+        }
+    }
+}
+
+/// Аугментация при детекции (TTA): изображение обрабатывается с несколькими
+/// размерами входа и, если `flip`, дополнительно отраженным по горизонтали.
+/// Результаты проходов объединяются взвешенным слиянием рамок с порогом `fusion_iou`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct TtaOptions {
+    /// Множители размера входа детектора.
+    pub scales: Vec<f32>,
+    pub flip: bool,
+    pub fusion_iou: f32,
+}
+
+impl Default for TtaOptions {
+    fn default() -> Self {
+        TtaOptions {
+            scales: vec![0.5, 1., 1.5],
+            flip: true,
+            fusion_iou: 0.55,
+        }
+    }
+}
+
+impl TtaOptions {
+    /// Возвращает описания недопустимых значений параметров.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.scales.is_empty() {
+            problems.push("tta scales must not be empty".to_string());
+        }
+        if self
+            .scales
+            .iter()
+            .any(|scale| scale.is_nan() || *scale <= 0.)
+        {
+            problems.push(format!(
+                "tta scales must be positive, got {:?}",
+                self.scales
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.fusion_iou) {
+            problems.push(format!(
+                "tta fusion_iou must be in [0, 1], got {}",
+                self.fusion_iou
+            ));
+        }
+
+        problems
+    }
+}
+
+/// Параметры постобработки детектора лиц.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct DetectionOptions {
+    /// Минимальная оценка найденного лица.
+    pub score_threshold: f32,
+    /// Порог IoU, выше которого менее уверенная из пересекающихся рамок отбрасывается.
+    pub nms_iou: f32,
+    /// Максимальное количество лиц в ответе (самые уверенные). По умолчанию не ограничено.
+    pub max_faces: Option<usize>,
+    /// Минимальная меньшая сторона рамки лица в пикселях исходного изображения.
+    pub min_face_size: f32,
+    /// Аугментация при детекции по параметрам [`TtaOptions`] модели.
+    pub tta: bool,
+    pub suppression: Suppression,
+    /// Параметр `sigma` гауссова Soft-NMS.
+    pub soft_nms_sigma: f32,
+}
+
+/// Способ подавления пересекающихся рамок.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Suppression {
+    /// Из рамок, пересекающихся сильнее `nms_iou`, остается самая уверенная.
+    #[default]
+    Nms,
+    /// Soft-NMS: оценки рамок, пересекающихся сильнее `nms_iou`, умножаются на `1 - IoU`.
+    SoftLinear,
+    /// Soft-NMS: оценки пересекающихся рамок умножаются на `exp(-IoU² / soft_nms_sigma)`.
+    SoftGaussian,
+    /// Рамки и ключевые точки, пересекающиеся сильнее `nms_iou`, усредняются с весами-оценками.
+    Wbf,
+}
+
+impl Default for DetectionOptions {
+    fn default() -> Self {
+        DetectionOptions {
+            score_threshold: 0.5,
+            nms_iou: 0.5,
+            max_faces: None,
+            min_face_size: 0.,
+            tta: false,
+            suppression: Suppression::Nms,
+            soft_nms_sigma: 0.5,
+        }
+    }
+}
+
+impl DetectionOptions {
+    /// Возвращает описания недопустимых значений параметров.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if !(0.0..=1.0).contains(&self.score_threshold) {
+            problems.push(format!(
+                "score_threshold must be in [0, 1], got {}",
+                self.score_threshold
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.nms_iou) {
+            problems.push(format!("nms_iou must be in [0, 1], got {}", self.nms_iou));
+        }
+        if self.max_faces == Some(0) {
+            problems.push("max_faces must be positive".to_string());
+        }
+        if self.soft_nms_sigma.is_nan() || self.soft_nms_sigma <= 0. {
+            problems.push(format!(
+                "soft_nms_sigma must be positive, got {}",
+                self.soft_nms_sigma
+            ));
+        }
+        if self.min_face_size.is_nan() || self.min_face_size < 0. {
+            problems.push(format!(
+                "min_face_size must be non-negative, got {}",
+                self.min_face_size
+            ));
+        }
+
+        problems
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizationLevel {
+    #[default]
+    Disable,
+    Level1,
+    Level2,
+    Level3,
+}
+
+/// Модели одной задачи: одна модель или несколько именованных моделей,
+/// одна из которых отмечена как модель по умолчанию.
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum ModelSet {
+    Single(ModelData),
+    Named(BTreeMap<String, ModelData>),
+}
+
+/// Раздел с `model_path` — единственная модель, иначе — именованные модели.
+/// Ошибка разбора выбранного варианта возвращается как есть, с именем поля.
+impl<'de> Deserialize<'de> for ModelSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let table = toml::Table::deserialize(deserializer)?;
+
+        match table.contains_key("model_path") {
+            true => ModelData::deserialize(table).map(ModelSet::Single),
+            false => BTreeMap::deserialize(table).map(ModelSet::Named),
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+impl ModelSet {
+    /// Модели вместе с их именами. Единственная модель называется по `model_name`.
+    pub fn models(&self) -> Vec<(&str, &ModelData)> {
+        match self {
+            ModelSet::Single(model) => vec![(model.model_name.as_str(), model)],
+            ModelSet::Named(models) => models
+                .iter()
+                .map(|(name, model)| (name.as_str(), model))
+                .collect(),
+        }
+    }
+
+    /// Имя модели по умолчанию. Если модель одна, отмечать ее не обязательно.
+    pub fn default_name(&self) -> Result<&str, String> {
+        let models = self.models();
+        if let [(name, _)] = models.as_slice() {
+            return Ok(name);
+        }
+
+        let defaults: Vec<&str> = models
+            .iter()
+            .filter(|(_, model)| model.default)
+            .map(|(name, _)| *name)
+            .collect();
+
+        match defaults.as_slice() {
+            [name] => Ok(name),
+            [] => Err("one of the models must be marked as `default = true`".into()),
+            _ => Err(format!("several default models: {}", defaults.join(", "))),
+        }
+    }
+}
+
+impl From<ModelData> for ModelSet {
+    fn from(model: ModelData) -> Self {
+        ModelSet::Single(model)
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Search {
-    pub visual: ModelData,
-    pub textual: ModelData,
+    pub visual: ModelSet,
+    pub textual: ModelSet,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct FacialProcessing {
-    pub detector: ModelData,
-    pub recognizer: ModelData,
+    pub detector: ModelSet,
+    pub recognizer: ModelSet,
 }
 
+/// Модели сервиса. Каждый раздел необязателен: маршруты регистрируются
+/// только для указанных моделей.
 #[derive(Debug, Deserialize, Clone)]
 pub struct Model {
-    pub facial_processing: FacialProcessing,
-    pub search: Search,
+    pub facial_processing: Option<FacialProcessing>,
+    pub search: Option<Search>,
+}
+
+impl Model {
+    /// Указанные в конфигурации модели с подписями вида `задача/имя`.
+    pub fn configured(&self) -> Vec<(String, &ModelData)> {
+        let mut tasks = vec![];
+
+        if let Some(facial_processing) = &self.facial_processing {
+            tasks.push(("detector", &facial_processing.detector));
+            tasks.push(("recognizer", &facial_processing.recognizer));
+        }
+        if let Some(search) = &self.search {
+            tasks.push(("textual", &search.textual));
+            tasks.push(("visual", &search.visual));
+        }
+
+        tasks
+            .into_iter()
+            .flat_map(|(task, models)| {
+                models
+                    .models()
+                    .into_iter()
+                    .map(move |(name, model)| (format!("{task}/{name}"), model))
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -30,20 +519,58 @@ pub struct Service {
     pub port: u16,
     pub swagger_path: String,
     pub body_limit: u32,
+    /// Количество потоков пула инференса. По умолчанию равно количеству ядер процессора.
+    pub inference_threads: Option<usize>,
+    /// Максимальное количество изображений в одном запросе к `/clip-visual/batch`.
+    #[serde(default = "default_max_images_per_request")]
+    pub max_images_per_request: usize,
+    /// Интервал проверки изменений файла конфигурации в секундах.
+    /// Если не указан, модели перезагружаются только через `POST /admin/reload`.
+    pub config_watch_interval_secs: Option<u64>,
+    /// Токен для `POST /admin/reload`, передается в заголовке `Authorization: Bearer <токен>`.
+    /// Если не указан, маршрут не регистрируется.
+    pub admin_token: Option<String>,
+}
+
+fn default_max_images_per_request() -> usize {
+    64
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub service: Service,
     pub model: Model,
+    /// Файл, из которого прочитана конфигурация.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
 }
 
 impl Config {
     pub fn new(config_file_name: &str) -> Self {
-        let config_file = std::env::var("CONFIG_FILE").unwrap_or(config_file_name.into());
-        let config_file = std::path::Path::new(&config_file);
-        let config = std::fs::read_to_string(config_file).unwrap();
-        let config: Config = toml::from_str(&config).unwrap();
-        config
+        Self::from_file(&Self::file_path(config_file_name)).unwrap()
+    }
+
+    /// Путь к файлу конфигурации: переменная окружения `CONFIG_FILE` или `config_file_name`.
+    pub fn file_path(config_file_name: &str) -> PathBuf {
+        std::env::var("CONFIG_FILE")
+            .unwrap_or(config_file_name.into())
+            .into()
+    }
+
+    pub fn from_file(config_file: &Path) -> Result<Self, Error> {
+        let config = std::fs::read_to_string(config_file).map_err(|err| {
+            Error::Config(format!("failed to read `{}`: {err}", config_file.display()))
+        })?;
+        let config: Config = toml::from_str(&config).map_err(|err| {
+            Error::Config(format!(
+                "failed to parse `{}`: {err}",
+                config_file.display()
+            ))
+        })?;
+
+        Ok(Config {
+            path: Some(config_file.to_path_buf()),
+            ..config
+        })
     }
 }
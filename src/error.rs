@@ -0,0 +1,222 @@
+use axum::{
+    extract::{
+        multipart::{MultipartError, MultipartRejection},
+        rejection::{JsonRejection, QueryRejection},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_typed_multipart::TypedMultipartError;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Загруженный файл не удалось распознать как изображение.
+    #[error("failed to decode image")]
+    ImageDecode(#[from] image::ImageError),
+
+    /// Запрос к служебному маршруту без верного токена.
+    #[error("missing or invalid admin token")]
+    Unauthorized,
+
+    /// Некорректные входные данные запроса.
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    /// Запрос отклонен при разборе параметров или тела с кодом ответа `status`,
+    /// например 413 для слишком большого тела или 415 для неверного типа содержимого.
+    #[error("invalid request: {message}")]
+    Rejected { status: StatusCode, message: String },
+
+    /// Для найденного лица невозможно вычислить выравнивание.
+    #[error("failed to align face: {0}")]
+    FaceAlignment(String),
+
+    /// Файл модели не удалось прочитать или загрузить.
+    #[error("failed to load model `{path}`")]
+    ModelLoad {
+        path: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// Хеш файла модели не совпадает с указанным в конфигурации.
+    #[error("model file `{path}` has sha256 {actual}, expected {expected}")]
+    ModelIntegrity {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// Ошибка во время работы ONNX Runtime.
+    #[error("inference failed")]
+    Inference(#[from] ort::Error),
+
+    /// Выход модели не соответствует ожидаемому формату.
+    #[error("unexpected model output: {0}")]
+    ModelOutput(String),
+
+    /// Ошибка токенизатора.
+    #[error("tokenizer error")]
+    Tokenizer(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// Батч, в который попал запрос, не удалось обработать.
+    #[error("batch inference failed: {0}")]
+    Batch(String),
+
+    /// Ошибка обработки батча, общая для всех запросов, попавших в него.
+    #[error(transparent)]
+    Batched(Arc<Error>),
+
+    /// Запрошенная модель не указана в конфигурации.
+    #[error("model `{0}` is not configured")]
+    UnknownModel(String),
+
+    /// Некорректная конфигурация сервиса.
+    #[error("invalid configuration: {0}")]
+    Config(String),
+
+    /// Модели не соответствуют ожиданиям сервиса.
+    #[error("model validation failed:\n{0}")]
+    ModelValidation(String),
+
+    /// Задача в пуле инференса завершилась паникой.
+    #[error("inference worker panicked")]
+    WorkerPanicked,
+}
+
+impl Error {
+    /// Ошибка разбора запроса с кодом ответа, который вычислил экстрактор axum.
+    fn rejected(status: StatusCode, message: String) -> Self {
+        Error::Rejected { status, message }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Error::Batched(err) => err.status_code(),
+            Error::ImageDecode(_) | Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            Error::Rejected { status, .. } => *status,
+            Error::FaceAlignment(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::UnknownModel(_) => StatusCode::NOT_FOUND,
+            Error::Unauthorized => StatusCode::UNAUTHORIZED,
+            Error::ModelLoad { .. }
+            | Error::ModelIntegrity { .. }
+            | Error::Inference(_)
+            | Error::ModelOutput(_)
+            | Error::Tokenizer(_)
+            | Error::Batch(_)
+            | Error::Config(_)
+            | Error::ModelValidation(_)
+            | Error::WorkerPanicked => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Batched(err) => err.code(),
+            Error::ImageDecode(_) => "image_decode",
+            Error::Unauthorized => "unauthorized",
+            Error::InvalidInput(_) => "invalid_input",
+            Error::Rejected { status, .. } => match *status {
+                StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+                _ => "invalid_input",
+            },
+            Error::FaceAlignment(_) => "face_alignment",
+            Error::ModelLoad { .. } => "model_load",
+            Error::ModelIntegrity { .. } => "model_integrity",
+            Error::Inference(_) => "inference",
+            Error::ModelOutput(_) => "model_output",
+            Error::Tokenizer(_) => "tokenizer",
+            Error::Batch(_) => "batch",
+            Error::UnknownModel(_) => "unknown_model",
+            Error::Config(_) => "config",
+            Error::ModelValidation(_) => "model_validation",
+            Error::WorkerPanicked => "worker_panicked",
+        }
+    }
+
+    fn details(&self) -> Option<String> {
+        match self {
+            Error::Batched(err) => err.details(),
+            Error::ImageDecode(err) => Some(err.to_string()),
+            Error::ModelLoad { source, .. } => Some(source.to_string()),
+            Error::Inference(err) => Some(err.to_string()),
+            Error::Tokenizer(err) => Some(err.to_string()),
+            Error::Unauthorized
+            | Error::InvalidInput(_)
+            | Error::Rejected { .. }
+            | Error::FaceAlignment(_)
+            | Error::ModelIntegrity { .. }
+            | Error::ModelOutput(_)
+            | Error::Batch(_)
+            | Error::UnknownModel(_)
+            | Error::Config(_)
+            | Error::ModelValidation(_)
+            | Error::WorkerPanicked => None,
+        }
+    }
+}
+
+/// Тело ответа с описанием ошибки.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Машиночитаемый код ошибки.
+    #[schema(example = "image_decode")]
+    pub code: String,
+    /// Описание ошибки.
+    #[schema(example = "failed to decode image")]
+    pub message: String,
+    /// Подробности об ошибке, если они есть.
+    pub details: Option<String>,
+}
+
+impl From<&Error> for ErrorResponse {
+    fn from(err: &Error) -> Self {
+        ErrorResponse {
+            code: err.code().to_string(),
+            message: err.to_string(),
+            details: err.details(),
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        (self.status_code(), Json(ErrorResponse::from(&self))).into_response()
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(rejection: QueryRejection) -> Self {
+        Error::rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<JsonRejection> for Error {
+    fn from(rejection: JsonRejection) -> Self {
+        Error::rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<MultipartRejection> for Error {
+    fn from(rejection: MultipartRejection) -> Self {
+        Error::rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<MultipartError> for Error {
+    fn from(err: MultipartError) -> Self {
+        Error::rejected(err.status(), err.body_text())
+    }
+}
+
+impl From<TypedMultipartError> for Error {
+    fn from(err: TypedMultipartError) -> Self {
+        Error::rejected(err.get_status(), err.to_string())
+    }
+}
@@ -1,4 +1,5 @@
 pub mod config;
+pub mod error;
 pub mod ml;
 pub mod models;
 pub mod router;
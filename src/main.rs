@@ -1,4 +1,5 @@
 pub mod config;
+pub mod error;
 pub mod ml;
 pub mod models;
 pub mod router;
@@ -7,6 +8,11 @@ pub mod router;
 async fn main() {
     env_logger::init();
 
+    if std::env::args().any(|arg| arg == "--check-config") {
+        check_config(&config::Config::file_path("config.toml"));
+        return;
+    }
+
     let config = config::Config::new("config.toml");
 
     let addr = format!("{}:{}", config.service.host, config.service.port);
@@ -15,8 +21,30 @@ async fn main() {
         config.service.swagger_path.clone(),
         config.service.body_limit,
         config.clone(),
-    );
+    )
+    .unwrap();
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
+
+/// Читает конфигурацию, загружает и проверяет модели, печатает отчет и завершает работу.
+fn check_config(config_file: &std::path::Path) {
+    match config::Config::from_file(config_file).and_then(router::AppState::new) {
+        Ok(state) => {
+            print!("{}", state.validate());
+            println!("warmup: ok");
+        }
+        Err(err) => {
+            eprintln!("{err}");
+
+            let mut source = std::error::Error::source(&err);
+            while let Some(err) = source {
+                eprintln!("caused by: {err}");
+                source = err.source();
+            }
+
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use ndarray::{Array, ArrayViewD, Axis, Dimension, RemoveAxis};
+use tokio::sync::oneshot;
+
+use crate::{
+    config::BatchingOptions,
+    error::{Error, Result},
+};
+
+/// Планировщик динамических батчей.
+///
+/// Собирает элементы из параллельных запросов в течение `max_wait_ms` миллисекунд
+/// или пока их не наберется `max_batch_size`, запускает модель один раз на всем батче
+/// в собственном потоке и возвращает каждому запросу его результат. Запросы ожидают
+/// результат асинхронно и не занимают потоки пула инференса.
+#[derive(Debug)]
+pub struct Batcher<I, O> {
+    sender: Sender<BatchRequest<I, O>>,
+}
+
+#[derive(Debug)]
+struct BatchRequest<I, O> {
+    item: I,
+    respond: oneshot::Sender<Result<O>>,
+}
+
+impl<I, O> Batcher<I, O>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    pub fn new<F>(name: &str, options: &BatchingOptions, run_batch: F) -> Self
+    where
+        F: Fn(Vec<I>) -> Result<Vec<O>> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+
+        let max_batch_size = options.max_batch_size.max(1);
+        let max_wait = Duration::from_millis(options.max_wait_ms);
+
+        thread::Builder::new()
+            .name(format!("batcher-{name}"))
+            .spawn(move || batching_loop(receiver, max_batch_size, max_wait, run_batch))
+            .unwrap();
+
+        Batcher { sender }
+    }
+
+    /// Добавляет элемент в очередной батч и ожидает результат.
+    pub async fn submit(&self, item: I) -> Result<O> {
+        self.submit_many(vec![item])
+            .await?
+            .pop()
+            .ok_or_else(|| Error::Batch("batcher dropped the request".into()))
+    }
+
+    /// Добавляет все элементы в очередь одновременно и ожидает результаты
+    /// в исходном порядке. Элементы могут попасть в разные батчи.
+    pub async fn submit_many(&self, items: Vec<I>) -> Result<Vec<O>> {
+        let responses = items
+            .into_iter()
+            .map(|item| {
+                let (respond, response) = oneshot::channel();
+                self.sender
+                    .send(BatchRequest { item, respond })
+                    .map_err(|_| Error::Batch("batcher is stopped".into()))?;
+                Ok(response)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut outputs = Vec::with_capacity(responses.len());
+        for response in responses {
+            outputs.push(
+                response
+                    .await
+                    .map_err(|_| Error::Batch("batcher dropped the request".into()))??,
+            );
+        }
+
+        Ok(outputs)
+    }
+}
+
+fn batching_loop<I, O, F>(
+    receiver: Receiver<BatchRequest<I, O>>,
+    max_batch_size: usize,
+    max_wait: Duration,
+    run_batch: F,
+) where
+    F: Fn(Vec<I>) -> Result<Vec<O>>,
+{
+    while let Ok(first) = receiver.recv() {
+        let deadline = Instant::now() + max_wait;
+        let mut batch = vec![first];
+
+        while batch.len() < max_batch_size {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(timeout) {
+                Ok(request) => batch.push(request),
+                Err(_) => break,
+            }
+        }
+
+        let (items, responders): (Vec<I>, Vec<oneshot::Sender<Result<O>>>) = batch
+            .into_iter()
+            .map(|request| (request.item, request.respond))
+            .unzip();
+
+        let result = catch_unwind(AssertUnwindSafe(|| run_batch(items)))
+            .unwrap_or_else(|_| Err(Error::WorkerPanicked));
+
+        match result {
+            Ok(outputs) if outputs.len() == responders.len() => {
+                for (respond, output) in responders.into_iter().zip(outputs) {
+                    let _ = respond.send(Ok(output));
+                }
+            }
+            Ok(outputs) => {
+                let message = format!(
+                    "batch returned {} results for {} items",
+                    outputs.len(),
+                    responders.len()
+                );
+                for respond in responders {
+                    let _ = respond.send(Err(Error::Batch(message.clone())));
+                }
+            }
+            // Единственный запрос получает ошибку как есть, остальные — общую ошибку батча
+            Err(err) if responders.len() == 1 => {
+                if let Some(respond) = responders.into_iter().next() {
+                    let _ = respond.send(Err(err));
+                }
+            }
+            Err(err) => {
+                let err = Arc::new(err);
+                for respond in responders {
+                    let _ = respond.send(Err(Error::Batched(err.clone())));
+                }
+            }
+        }
+    }
+}
+
+/// Объединяет тензоры `[1, ...]` в один тензор `[B, ...]`.
+pub fn stack_batch<D: Dimension + RemoveAxis>(tensors: &[Array<f32, D>]) -> Result<Array<f32, D>> {
+    let views: Vec<_> = tensors.iter().map(|tensor| tensor.view()).collect();
+
+    ndarray::concatenate(Axis(0), &views).map_err(|err| Error::InvalidInput(err.to_string()))
+}
+
+/// Разбивает выход модели `[B, D]` на `B` векторов.
+pub fn split_rows(tensor: ArrayViewD<f32>, batch_size: usize) -> Result<Vec<Vec<f32>>> {
+    if tensor.ndim() != 2 || tensor.shape()[0] != batch_size {
+        return Err(Error::ModelOutput(format!(
+            "expected output with shape [{batch_size}, D], got {:?}",
+            tensor.shape()
+        )));
+    }
+
+    Ok(tensor.rows().into_iter().map(|row| row.to_vec()).collect())
+}
@@ -0,0 +1,469 @@
+use ndarray::{array, concatenate, Array2, ArrayView2, ArrayViewD, Axis, Ix2};
+use ort::DynValue;
+
+use crate::{
+    config::{GridOptions, HeadOptions},
+    error::{Error, Result},
+    ml::{
+        predictor::TensorInfo,
+        validation::{check_count, check_tensor},
+    },
+    models::DetectedFaceOutput,
+};
+
+/// Декодер выходов детектора в лица с координатами во входе модели.
+///
+/// Выходы могут иметь ведущую размерность батча `1`, она отбрасывается.
+#[derive(Debug, Clone)]
+pub enum DetectorHead {
+    Scrfd(ScrfdHead),
+    Yolov8(Yolov8Head),
+}
+
+/// Выходы SCRFD с якорями в узлах сетки для каждого шага.
+#[derive(Debug, Clone)]
+pub struct ScrfdHead {
+    num_anchors: usize,
+    levels: Vec<ScrfdLevel>,
+    /// Ожидаемое количество выходов, если они берутся по порядку.
+    positional_outputs: Option<usize>,
+}
+
+/// Номера выходов одного шага сетки и центры его якорей.
+#[derive(Debug, Clone)]
+struct ScrfdLevel {
+    stride: usize,
+    /// Центры якорей `[N, 2]` для текущего размера входа.
+    centers: Array2<f32>,
+    scores: usize,
+    bboxes: usize,
+    keypoints: Option<usize>,
+}
+
+/// Выход YOLOv8-face с уже декодированными рамками.
+#[derive(Debug, Clone)]
+pub struct Yolov8Head {
+    output: usize,
+    keypoints: bool,
+}
+
+impl DetectorHead {
+    /// Сопоставляет выходы модели `outputs` с форматом `head`.
+    pub fn new(head: &HeadOptions, grid: &GridOptions, outputs: &[TensorInfo]) -> Result<Self> {
+        match head {
+            HeadOptions::Scrfd {
+                keypoints,
+                outputs: names,
+            } => {
+                let count = grid.strides.len();
+                let (scores, bboxes, kpsses) = match names {
+                    Some(names) => (
+                        output_indices(outputs, "scores", &names.scores, count)?,
+                        output_indices(outputs, "bboxes", &names.bboxes, count)?,
+                        match keypoints {
+                            true => output_indices(outputs, "keypoints", &names.keypoints, count)?,
+                            false => vec![],
+                        },
+                    ),
+                    None => (
+                        (0..count).collect(),
+                        (count..count * 2).collect(),
+                        match keypoints {
+                            true => (count * 2..count * 3).collect(),
+                            false => vec![],
+                        },
+                    ),
+                };
+
+                let (width, height) = grid.input_size.dimensions();
+                let levels = grid
+                    .strides
+                    .iter()
+                    .enumerate()
+                    .map(|(level, &stride)| ScrfdLevel {
+                        stride,
+                        centers: anchor_center_array(width, height, stride, grid.num_anchors),
+                        scores: scores[level],
+                        bboxes: bboxes[level],
+                        keypoints: kpsses.get(level).copied(),
+                    })
+                    .collect();
+
+                Ok(DetectorHead::Scrfd(ScrfdHead {
+                    num_anchors: grid.num_anchors,
+                    levels,
+                    positional_outputs: names
+                        .is_none()
+                        .then_some(count * if *keypoints { 3 } else { 2 }),
+                }))
+            }
+            HeadOptions::Yolov8 { keypoints, output } => Ok(DetectorHead::Yolov8(Yolov8Head {
+                output: match output {
+                    Some(name) => output_index(outputs, name)?,
+                    None => 0,
+                },
+                keypoints: *keypoints,
+            })),
+        }
+    }
+
+    /// Тот же формат выходов для входа модели другого размера.
+    pub fn with_input_size(&self, input_size: (u32, u32)) -> Self {
+        match self {
+            DetectorHead::Scrfd(head) => {
+                let (width, height) = input_size;
+                DetectorHead::Scrfd(ScrfdHead {
+                    levels: head
+                        .levels
+                        .iter()
+                        .map(|level| ScrfdLevel {
+                            centers: anchor_center_array(
+                                width,
+                                height,
+                                level.stride,
+                                head.num_anchors,
+                            ),
+                            ..level.clone()
+                        })
+                        .collect(),
+                    ..head.clone()
+                })
+            }
+            DetectorHead::Yolov8(head) => DetectorHead::Yolov8(head.clone()),
+        }
+    }
+
+    /// Возвращает лица с оценкой выше `threshold` в координатах входа модели.
+    pub fn decode(&self, outputs: &[DynValue], threshold: f32) -> Result<Vec<DetectedFaceOutput>> {
+        match self {
+            DetectorHead::Scrfd(head) => head.decode(outputs, threshold),
+            DetectorHead::Yolov8(head) => head.decode(outputs, threshold),
+        }
+    }
+
+    /// Проверяет, что выходы модели соответствуют формату.
+    pub fn validate(&self, outputs: &[TensorInfo]) -> Vec<String> {
+        match self {
+            DetectorHead::Scrfd(head) => head.validate(outputs),
+            DetectorHead::Yolov8(head) => head.validate(outputs),
+        }
+    }
+}
+
+impl ScrfdHead {
+    fn decode(&self, outputs: &[DynValue], threshold: f32) -> Result<Vec<DetectedFaceOutput>> {
+        let mut faces = vec![];
+
+        for level in &self.levels {
+            let count = level.centers.nrows();
+
+            let scores = extract_tensor(outputs, level.scores, [count, 1])?;
+            let bboxes = extract_tensor(outputs, level.bboxes, [count, 4])?;
+            let kpsses = level
+                .keypoints
+                .map(|index| extract_tensor(outputs, index, [count, 10]))
+                .transpose()?;
+
+            faces.extend(decode_anchors(
+                level.centers.view(),
+                level.stride,
+                scores,
+                bboxes,
+                kpsses,
+                threshold,
+            ));
+        }
+
+        Ok(faces)
+    }
+
+    fn validate(&self, outputs: &[TensorInfo]) -> Vec<String> {
+        if let Some(problem) = self
+            .positional_outputs
+            .and_then(|expected| check_count("output", outputs, expected))
+        {
+            return vec![problem];
+        }
+
+        self.levels
+            .iter()
+            .flat_map(|level| {
+                let count = level.centers.nrows();
+
+                [
+                    Some((level.scores, 1)),
+                    Some((level.bboxes, 4)),
+                    level.keypoints.map(|index| (index, 10)),
+                ]
+                .into_iter()
+                .flatten()
+                .filter_map(move |(index, columns)| {
+                    check_output(outputs, index, &[count as i64, columns])
+                })
+            })
+            .collect()
+    }
+}
+
+impl Yolov8Head {
+    fn decode(&self, outputs: &[DynValue], threshold: f32) -> Result<Vec<DetectedFaceOutput>> {
+        let tensor = batchless(extract_output(outputs, self.output)?);
+
+        if tensor.ndim() != 2 {
+            return Err(Error::ModelOutput(format!(
+                "YOLOv8 detector output must have shape [C, N], got {:?}",
+                tensor.shape()
+            )));
+        }
+
+        // Модель может возвращать как `[C, N]`, так и `[N, C]`; каналов всегда меньше, чем кандидатов
+        let tensor = match tensor.shape()[0] <= tensor.shape()[1] {
+            true => tensor,
+            false => tensor.reversed_axes(),
+        };
+
+        if tensor.shape()[0] != self.channels() {
+            return Err(Error::ModelOutput(format!(
+                "YOLOv8 detector output must have {} channels, got {}",
+                self.channels(),
+                tensor.shape()[0]
+            )));
+        }
+
+        let mut faces = vec![];
+        for index in 0..tensor.shape()[1] {
+            let score = tensor[[4, index]];
+            if score > threshold {
+                let (x, y) = (tensor[[0, index]], tensor[[1, index]]);
+                let (half_width, half_height) = (tensor[[2, index]] / 2., tensor[[3, index]] / 2.);
+
+                faces.push(DetectedFaceOutput::new(
+                    score,
+                    [
+                        x - half_width,
+                        y - half_height,
+                        x + half_width,
+                        y + half_height,
+                    ],
+                    self.keypoints.then(|| {
+                        std::array::from_fn(|point| {
+                            (
+                                tensor[[5 + point * 3, index]],
+                                tensor[[6 + point * 3, index]],
+                            )
+                        })
+                    }),
+                ));
+            }
+        }
+
+        Ok(faces)
+    }
+
+    fn validate(&self, outputs: &[TensorInfo]) -> Vec<String> {
+        let Some(output) = outputs.get(self.output) else {
+            return vec![format!(
+                "output #{} is missing, model has {} outputs",
+                self.output,
+                outputs.len()
+            )];
+        };
+
+        let shape = match output.shape.as_slice() {
+            [1 | -1, rest @ ..] if rest.len() == 2 => rest,
+            shape => shape,
+        };
+        // Каналы — меньшая из фиксированных размерностей; если обе динамические, форма
+        // проверяется при декодировании
+        let channels = shape
+            .iter()
+            .copied()
+            .filter(|&dimension| dimension > 0)
+            .min();
+        let channels_match =
+            shape.len() == 2 && channels.is_none_or(|channels| channels == self.channels() as i64);
+
+        match channels_match {
+            true => vec![],
+            false => vec![format!(
+                "output #{} `{}` has shape {:?}, expected [{}, N]",
+                self.output,
+                output.name,
+                output.shape,
+                self.channels()
+            )],
+        }
+    }
+
+    /// Рамка, оценка и, если `keypoints`, пять точек `(x, y, видимость)`.
+    fn channels(&self) -> usize {
+        match self.keypoints {
+            true => 20,
+            false => 5,
+        }
+    }
+}
+
+/// Центры якорей шага `stride` для входа `width x height`.
+///
+/// Узлы сетки перечисляются по строкам, в каждом узле `num_anchors` якорей
+/// с одинаковым центром.
+pub fn anchor_centers(
+    width: u32,
+    height: u32,
+    stride: usize,
+    num_anchors: usize,
+) -> Vec<(f32, f32)> {
+    let (columns, rows) = (width as usize / stride, height as usize / stride);
+
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .flat_map(|(column, row)| {
+            let center = ((column * stride) as f32, (row * stride) as f32);
+            std::iter::repeat_n(center, num_anchors)
+        })
+        .collect()
+}
+
+/// Центры якорей [`anchor_centers`] в виде массива `[N, 2]`.
+pub fn anchor_center_array(
+    width: u32,
+    height: u32,
+    stride: usize,
+    num_anchors: usize,
+) -> Array2<f32> {
+    let centers = anchor_centers(width, height, stride, num_anchors);
+    let count = centers.len();
+
+    Array2::from_shape_vec(
+        (count, 2),
+        centers.into_iter().flat_map(|(x, y)| [x, y]).collect(),
+    )
+    .unwrap()
+}
+
+/// Декодирует выходы SCRFD одного шага сетки в лица с координатами во входе модели.
+///
+/// Сначала по оценкам `scores` `[N, 1]` выбираются якоря выше `threshold`, затем
+/// рамки `bboxes` `[N, 4]` и ключевые точки `keypoints` `[N, 10]` декодируются
+/// только для них операциями над массивами целиком.
+pub fn decode_anchors(
+    centers: ArrayView2<f32>,
+    stride: usize,
+    scores: ArrayView2<f32>,
+    bboxes: ArrayView2<f32>,
+    keypoints: Option<ArrayView2<f32>>,
+    threshold: f32,
+) -> Vec<DetectedFaceOutput> {
+    let survivors: Vec<usize> = scores
+        .column(0)
+        .indexed_iter()
+        .filter(|(_, &score)| score > threshold)
+        .map(|(index, _)| index)
+        .collect();
+
+    if survivors.is_empty() {
+        return vec![];
+    }
+
+    let stride = stride as f32;
+    let centers = centers.select(Axis(0), &survivors);
+
+    let boxes = concatenate(Axis(1), &[centers.view(); 2]).unwrap()
+        + bboxes.select(Axis(0), &survivors) * array![-stride, -stride, stride, stride];
+    let landmarks = keypoints.map(|keypoints| {
+        concatenate(Axis(1), &[centers.view(); 5]).unwrap()
+            + keypoints.select(Axis(0), &survivors) * stride
+    });
+
+    survivors
+        .iter()
+        .zip(boxes.rows())
+        .enumerate()
+        .map(|(row, (&index, bbox))| {
+            DetectedFaceOutput::new(
+                scores[[index, 0]],
+                std::array::from_fn(|column| bbox[column]),
+                landmarks.as_ref().map(|landmarks| {
+                    let points = landmarks.row(row);
+                    std::array::from_fn(|point| (points[point * 2], points[point * 2 + 1]))
+                }),
+            )
+        })
+        .collect()
+}
+
+fn output_index(outputs: &[TensorInfo], name: &str) -> Result<usize> {
+    outputs
+        .iter()
+        .position(|output| output.name == name)
+        .ok_or_else(|| Error::ModelOutput(format!("detector has no output `{name}`")))
+}
+
+fn output_indices(
+    outputs: &[TensorInfo],
+    kind: &str,
+    names: &[String],
+    count: usize,
+) -> Result<Vec<usize>> {
+    if names.len() != count {
+        return Err(Error::Config(format!(
+            "detector head must name {count} `{kind}` outputs, one per stride, got {}",
+            names.len()
+        )));
+    }
+
+    names
+        .iter()
+        .map(|name| output_index(outputs, name))
+        .collect()
+}
+
+/// Проверяет форму выхода с учетом возможной размерности батча.
+fn check_output(outputs: &[TensorInfo], index: usize, shape: &[i64]) -> Option<String> {
+    match outputs.get(index).map(|output| output.shape.len()) {
+        Some(rank) if rank == shape.len() + 1 => {
+            check_tensor("output", outputs, index, &[&[1], shape].concat())
+        }
+        _ => check_tensor("output", outputs, index, shape),
+    }
+}
+
+fn extract_output(outputs: &[DynValue], index: usize) -> Result<ArrayViewD<'_, f32>> {
+    let output = outputs.get(index).ok_or_else(|| {
+        Error::ModelOutput(format!(
+            "detector output {index} is missing, got {} outputs",
+            outputs.len()
+        ))
+    })?;
+
+    Ok(output.try_extract_tensor::<f32>()?)
+}
+
+/// Отбрасывает ведущую размерность батча `1`, если она есть.
+fn batchless(tensor: ArrayViewD<'_, f32>) -> ArrayViewD<'_, f32> {
+    match tensor.shape() {
+        [1, _, _] => tensor.index_axis_move(Axis(0), 0),
+        _ => tensor,
+    }
+}
+
+/// Извлекает выход детектора с номером `index` и проверяет, что его форма равна `shape`.
+fn extract_tensor(
+    outputs: &[DynValue],
+    index: usize,
+    shape: [usize; 2],
+) -> Result<ArrayView2<'_, f32>> {
+    let tensor = batchless(extract_output(outputs, index)?);
+
+    if tensor.shape() != shape {
+        return Err(Error::ModelOutput(format!(
+            "detector output {index} must have shape {shape:?}, got {:?}",
+            tensor.shape()
+        )));
+    }
+
+    tensor
+        .into_dimensionality::<Ix2>()
+        .map_err(|error| Error::ModelOutput(error.to_string()))
+}
@@ -1,2 +1,5 @@
+pub mod head;
 pub mod post_processing;
 pub mod predictor;
+pub mod suppression;
+pub mod tiling;
@@ -1,204 +1,183 @@
-use image::DynamicImage;
-use ort::SessionOutputs;
-
-use crate::models::DetectedFaceOutput;
-
-const EPS: f32 = 1.0e-7;
+use ort::DynValue;
+
+use crate::{
+    config::{DetectionOptions, Suppression},
+    error::Result,
+    ml::facial_processing::{
+        detection::{
+            head::DetectorHead,
+            suppression::{
+                is_finite, non_maximum_suppression, soft_nms, weighted_box_fusion, SoftNmsDecay,
+            },
+        },
+        transforms::Letterbox,
+    },
+    models::DetectedFaceOutput,
+};
 
 pub fn post_processing(
-    outputs: SessionOutputs,
-    threshold: f32,
-    original_image: &DynamicImage,
+    outputs: &[DynValue],
+    head: &DetectorHead,
+    options: &DetectionOptions,
+    letterbox: &Letterbox,
+) -> Result<Vec<DetectedFaceOutput>> {
+    let faces = candidates(outputs, head, options.score_threshold, letterbox)?;
+
+    Ok(final_faces(faces, options, letterbox.source_size))
+}
+
+/// Выбирает лица [`select_faces`] и обрезает их рамки границами изображения `image_size`.
+///
+/// Лица целиком вне изображения отбрасываются до ограничения количества `max_faces`.
+pub fn final_faces(
+    faces: Vec<DetectedFaceOutput>,
+    options: &DetectionOptions,
+    image_size: (u32, u32),
 ) -> Vec<DetectedFaceOutput> {
-    let mut faces: Vec<DetectedFaceOutput> = vec![];
-
-    let scores08 = &outputs[0].try_extract_tensor().unwrap();
-    let scores16 = &outputs[1].try_extract_tensor().unwrap();
-    let scores32 = &outputs[2].try_extract_tensor().unwrap();
-    let bboxes08 = &outputs[3].try_extract_tensor().unwrap();
-    let bboxes16 = &outputs[4].try_extract_tensor().unwrap();
-    let bboxes32 = &outputs[5].try_extract_tensor().unwrap();
-    let kpsses08 = &outputs[6].try_extract_tensor().unwrap();
-    let kpsses16 = &outputs[7].try_extract_tensor().unwrap();
-    let kpsses32 = &outputs[8].try_extract_tensor().unwrap();
-
-    for index in 0..12800 {
-        let score = scores08[[index, 0]];
-        if score > threshold {
-            let bbox = distance2bbox(index, 8, bboxes08);
-            let landmarks = distance2kps(index, 8, kpsses08);
-
-            faces.push(DetectedFaceOutput {
-                score,
-                bbox,
-                landmarks,
-            });
-        }
-    }
+    let unlimited = DetectionOptions {
+        max_faces: None,
+        ..options.clone()
+    };
 
-    for index in 0..3200 {
-        let score = scores16[[index, 0]];
-        if score > threshold {
-            let bbox = distance2bbox(index, 16, bboxes16);
-            let landmarks = distance2kps(index, 16, kpsses16);
-
-            faces.push(DetectedFaceOutput {
-                score,
-                bbox,
-                landmarks,
-            });
-        }
-    }
+    let mut faces: Vec<DetectedFaceOutput> = select_faces(faces, &unlimited)
+        .into_iter()
+        .map(|face| clip_to_image(face, image_size))
+        .filter(|face| box_area(&face.bbox) > 0.)
+        .collect();
 
-    for index in 0..800 {
-        let score = scores32[[index, 0]];
-        if score > threshold {
-            let bbox = distance2bbox(index, 32, bboxes32);
-            let landmarks = distance2kps(index, 32, kpsses32);
-
-            faces.push(DetectedFaceOutput {
-                score,
-                bbox,
-                landmarks,
-            });
-        }
+    if let Some(max_faces) = options.max_faces {
+        faces.truncate(max_faces);
     }
 
-    faces.sort_by(|a, b| (a.score.partial_cmp(&b.score).unwrap()));
-
-    let mut unique_faces = non_maximum_suppression(faces, 0.5);
+    faces
+}
 
-    normalize_coordinates(&mut unique_faces, original_image);
+/// Объединяет лица проходов аугментации взвешенным слиянием рамок с порогом `fusion_iou`
+/// и выбирает окончательные лица [`final_faces`].
+///
+/// Оценка лица, найденного не всеми проходами, уменьшается при слиянии, поэтому
+/// порог `options.score_threshold` применяется повторно к объединенным лицам.
+pub fn fused_faces(
+    passes: Vec<Vec<DetectedFaceOutput>>,
+    fusion_iou: f32,
+    options: &DetectionOptions,
+    image_size: (u32, u32),
+) -> Vec<DetectedFaceOutput> {
+    let mut faces = weighted_box_fusion(passes, fusion_iou);
+    faces.retain(|face| face.score > options.score_threshold);
 
-    unique_faces
+    final_faces(faces, options, image_size)
 }
 
-fn normalize_coordinates(faces: &mut Vec<DetectedFaceOutput>, original_image: &DynamicImage) {
-    let (orig_width, orig_height) = (
-        original_image.width() as f32,
-        original_image.height() as f32,
-    );
-
-    let ratio = if orig_width / orig_height > 1. {
-        orig_width / 640.
-    } else if orig_width / orig_height < 1. {
-        orig_height / 640.
-    } else {
-        1.
-    };
-
-    for face in faces {
-        face.bbox = face.bbox.map(|el| el * ratio);
-        face.landmarks = face.landmarks.map(|(x, y)| (x * ratio, y * ratio));
+/// Лица с оценкой выше `threshold` в координатах исходного изображения, до подавления.
+pub fn candidates(
+    outputs: &[DynValue],
+    head: &DetectorHead,
+    threshold: f32,
+    letterbox: &Letterbox,
+) -> Result<Vec<DetectedFaceOutput>> {
+    let mut faces = head.decode(outputs, threshold)?;
+
+    for face in &mut faces {
+        face.bbox = letterbox.inverse_bbox(face.bbox);
+        face.landmarks = face
+            .landmarks
+            .map(|landmarks| landmarks.map(|point| letterbox.inverse(point)));
     }
-}
 
-fn distance2bbox(
-    index: usize,
-    stride: usize,
-    distance: &ndarray::prelude::ArrayBase<
-        ndarray::ViewRepr<&f32>,
-        ndarray::prelude::Dim<ndarray::IxDynImpl>,
-    >,
-) -> [f32; 4] {
-    let m = 640 / stride;
-    let x = ((index / 2) * stride) % 640;
-    let y = (((index / 2) / m) * stride) % 640;
-
-    let x1 = x as f32 - distance[[index, 0]] * stride as f32;
-    let y1 = y as f32 - distance[[index, 1]] * stride as f32;
-
-    let x2 = x as f32 + distance[[index, 2]] * stride as f32;
-    let y2 = y as f32 + distance[[index, 3]] * stride as f32;
-
-    return [x1, y1, x2, y2];
+    Ok(faces)
 }
 
-fn distance2kps(
-    index: usize,
-    stride: usize,
-    distance: &ndarray::prelude::ArrayBase<
-        ndarray::ViewRepr<&f32>,
-        ndarray::prelude::Dim<ndarray::IxDynImpl>,
-    >,
-) -> [(f32, f32); 5] {
-    let m = 640 / stride;
-    let x = ((index / 2) * stride) % 640;
-    let y = (((index / 2) / m) * stride) % 640;
-
-    let x1 = x as f32 + distance[[index, 0]] * stride as f32;
-    let y1 = y as f32 + distance[[index, 1]] * stride as f32;
-
-    let x2 = x as f32 + distance[[index, 2]] * stride as f32;
-    let y2 = y as f32 + distance[[index, 3]] * stride as f32;
-
-    let x3 = x as f32 + distance[[index, 4]] * stride as f32;
-    let y3 = y as f32 + distance[[index, 5]] * stride as f32;
-
-    let x4 = x as f32 + distance[[index, 6]] * stride as f32;
-    let y4 = y as f32 + distance[[index, 7]] * stride as f32;
+/// Отбрасывает маленькие рамки, подавляет или объединяет пересекающиеся способом
+/// `options.suppression` и оставляет не более `max_faces` лиц, самые уверенные первыми.
+pub fn select_faces(
+    mut faces: Vec<DetectedFaceOutput>,
+    options: &DetectionOptions,
+) -> Vec<DetectedFaceOutput> {
+    // Рамки с нечисловыми координатами и слишком маленькие рамки отбрасываются
+    // до подавления, чтобы они не подавили соседние
+    faces.retain(|face| is_finite(face) && face_size(&face.bbox) >= options.min_face_size);
+
+    faces.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut unique_faces = match options.suppression {
+        Suppression::Nms => non_maximum_suppression(faces, options.nms_iou),
+        Suppression::SoftLinear => soft_nms(
+            faces,
+            SoftNmsDecay::Linear(options.nms_iou),
+            options.score_threshold,
+        ),
+        Suppression::SoftGaussian => soft_nms(
+            faces,
+            SoftNmsDecay::Gaussian(options.soft_nms_sigma),
+            options.score_threshold,
+        ),
+        Suppression::Wbf => weighted_box_fusion(vec![faces], options.nms_iou),
+    };
 
-    let x5 = x as f32 + distance[[index, 8]] * stride as f32;
-    let y5 = y as f32 + distance[[index, 9]] * stride as f32;
+    if let Some(max_faces) = options.max_faces {
+        unique_faces.truncate(max_faces);
+    }
 
-    return [(x1, y1), (x2, y2), (x3, y3), (x4, y4), (x5, y5)];
+    unique_faces
 }
 
-/// Запустите не максимальное подавление для возможных ограничивающих рамок.
+/// Отражает лицо, найденное на отраженном по горизонтали изображении ширины `image_width`,
+/// обратно в координаты исходного изображения.
 ///
-/// Пары ограничивающих рамок с доверием должны быть отсортированы в порядке возрастания
-/// достоверности, потому что мы хотим "вытащить()" наиболее достоверные элементы с обратной стороны.
-///
-/// Начните с наиболее достоверного ограничивающего прямоугольника и выполните итерацию по всем остальным ограничивающим прямоугольникам в
-/// порядке уменьшения достоверности. Увеличьте вектор выбранных ограничивающих прямоугольников, добавив только те, которые
-/// кандидаты, у которых количество баллов по долговой расписке не превышает "max_iou", с уже выбранными ограничивающими рамками.
-/// Выполняется итерация по всем ограничивающим рамкам в `sorted_boxes_with_confidences`. Все кандидаты, набравшие
-/// слишком низкие баллы, чтобы их можно было рассматривать, должны быть предварительно отфильтрованы.
-fn non_maximum_suppression(
-    mut sorted_bboxes_with_confidences: Vec<DetectedFaceOutput>,
-    max_iou: f32,
-) -> Vec<DetectedFaceOutput> {
-    let mut selected: Vec<DetectedFaceOutput> = vec![];
-    'candidates: loop {
-        match sorted_bboxes_with_confidences.pop() {
-            Some(face) => {
-                // Проверка на пересечение лиц
-                for selected_face in selected.iter() {
-                    match iou(&face.bbox, &selected_face.bbox) {
-                        x if x > max_iou => continue 'candidates,
-                        _ => (),
-                    }
-                }
-
-                selected.push(face.clone())
-            }
-            None => break 'candidates,
-        }
+/// При отражении левый и правый глаз, как и уголки рта, меняются местами.
+pub fn mirror_face(face: DetectedFaceOutput, image_width: f32) -> DetectedFaceOutput {
+    let [x1, y1, x2, y2] = face.bbox;
+
+    DetectedFaceOutput {
+        bbox: [image_width - x2, y1, image_width - x1, y2],
+        landmarks: face.landmarks.map(|landmarks| {
+            let [left_eye, right_eye, nose, left_mouth, right_mouth] =
+                landmarks.map(|(x, y)| (image_width - x, y));
+            [right_eye, left_eye, nose, right_mouth, left_mouth]
+        }),
+        ..face
     }
-
-    selected
 }
 
-/// Вычисление показателя пересечения над объединением для двух ограничивающих прямоугольников.
-fn iou(bbox_a: &[f32; 4], bbox_b: &[f32; 4]) -> f32 {
-    let overlap_box: [f32; 4] = [
-        f32::max(bbox_a[0], bbox_b[0]),
-        f32::max(bbox_a[1], bbox_b[1]),
-        f32::min(bbox_a[2], bbox_b[2]),
-        f32::min(bbox_a[3], bbox_b[3]),
+/// Обрезает рамку лица границами изображения `width x height`.
+///
+/// Исходная рамка сохраняется в `unclipped_bbox`, `visibility` — доля ее площади
+/// внутри изображения. Применяется к окончательным лицам: подавление и слияние
+/// рамок работают с необрезанными рамками.
+pub fn clip_to_image(face: DetectedFaceOutput, (width, height): (u32, u32)) -> DetectedFaceOutput {
+    let [x1, y1, x2, y2] = face.bbox;
+    let (width, height) = (width as f32, height as f32);
+
+    let bbox = [
+        x1.clamp(0., width),
+        y1.clamp(0., height),
+        x2.clamp(0., width),
+        y2.clamp(0., height),
     ];
+    let truncated = bbox != face.bbox;
 
-    let overlap_area = bbox_area(&overlap_box);
+    let area = box_area(&face.bbox);
+    let visibility = match area > 0. {
+        true => box_area(&bbox) / area,
+        false if truncated => 0.,
+        false => 1.,
+    };
 
-    overlap_area / (bbox_area(bbox_a) + bbox_area(bbox_b) - overlap_area + EPS)
+    DetectedFaceOutput {
+        bbox,
+        unclipped_bbox: face.bbox,
+        truncated,
+        visibility,
+        ..face
+    }
 }
 
-fn bbox_area(bbox: &[f32; 4]) -> f32 {
-    let width = bbox[3] - bbox[1];
-    let height = bbox[2] - bbox[0];
-    if width < 0.0 || height < 0.0 {
-        return 0.0;
-    }
+fn box_area(bbox: &[f32; 4]) -> f32 {
+    (bbox[2] - bbox[0]).max(0.) * (bbox[3] - bbox[1]).max(0.)
+}
 
-    width * height
+/// Меньшая сторона рамки.
+fn face_size(bbox: &[f32; 4]) -> f32 {
+    f32::min(bbox[2] - bbox[0], bbox[3] - bbox[1])
 }
@@ -1,55 +1,299 @@
-use image::{DynamicImage, Rgba32FImage};
-use ndarray::Array;
-use ort::{inputs, GraphOptimizationLevel, Session};
+use std::{borrow::Cow, sync::Arc};
+
+use image::{DynamicImage, GenericImageView};
+use ndarray::Array4;
+use ort::{inputs, DynValue};
 
 use crate::{
-    ml::facial_processing::{detection::post_processing::post_processing, transforms::resize},
+    config::{DetectionOptions, GridOptions, ModelData, TilingOptions, TtaOptions},
+    error::Result,
+    ml::{
+        facial_processing::{
+            detection::{
+                head::DetectorHead,
+                post_processing::{
+                    candidates, final_faces, fused_faces, mirror_face, post_processing,
+                    select_faces,
+                },
+                tiling::tiles,
+            },
+            transforms::{normalized_tensor, resize, Letterbox},
+        },
+        fingerprint::ModelFingerprint,
+        predictor::{ModelMetadata, Predictor},
+        session_pool::{PoolStatus, SessionPool},
+        tasks::FaceDetection,
+        validation::{check_count, check_tensor},
+    },
     models::DetectedFaceOutput,
 };
 
 #[derive(Debug, Clone)]
 pub struct FaceDetector {
-    pub model_path: String,
-    pub model_name: String,
+    metadata: ModelMetadata,
+    grid: GridOptions,
+    head: DetectorHead,
+    /// Форматы выходов с центрами якорей для размеров входа TTA, отличных от основного.
+    tta_heads: Vec<((u32, u32), DetectorHead)>,
+    tiling: Option<TilingOptions>,
+    tta: TtaOptions,
+    options: DetectionOptions,
+    session: Arc<SessionPool>,
 }
 
 impl FaceDetector {
-    pub fn new(path: String, name: String) -> Self {
-        FaceDetector {
-            model_path: path,
-            model_name: name,
+    pub fn new(path: String, name: String) -> Result<Self> {
+        Self::from_config(ModelData::new(path, name))
+    }
+
+    pub fn from_config(config: ModelData) -> Result<Self> {
+        let fingerprint = ModelFingerprint::verify(&config)?;
+        let session = SessionPool::from_config(&config)?;
+
+        let head = DetectorHead::new(&config.head, &config.grid, session.outputs())?;
+
+        let mut detector = FaceDetector {
+            grid: config.grid.clone(),
+            head,
+            tta_heads: vec![],
+            tiling: config.tiling.clone(),
+            tta: config.tta.clone(),
+            options: config.detection.clone(),
+            metadata: ModelMetadata::new(config, &session, fingerprint),
+            session: Arc::new(session),
+        };
+
+        let input_size = detector.grid.input_size.dimensions();
+        detector.tta_heads = detector
+            .tta_input_sizes()
+            .into_iter()
+            .filter(|&size| size != input_size)
+            .map(|size| (size, detector.head.with_input_size(size)))
+            .collect();
+
+        Ok(detector)
+    }
+
+    pub fn pool_status(&self) -> PoolStatus {
+        self.session.status()
+    }
+
+    /// Аугментация при детекции: проходы с каждым масштабом входа, исходным
+    /// и отраженным изображением, объединенные взвешенным слиянием рамок.
+    ///
+    /// Если размер входа модели фиксирован, масштабы не применяются.
+    fn detect_tta(
+        &self,
+        image: &DynamicImage,
+        options: &DetectionOptions,
+    ) -> Result<Vec<DetectedFaceOutput>> {
+        let flipped = self.tta.flip.then(|| image.fliph());
+        let image_width = image.width() as f32;
+
+        // Ограничения размера и количества применяются только к объединенному результату
+        let pass_options = DetectionOptions {
+            max_faces: None,
+            min_face_size: 0.,
+            ..options.clone()
+        };
+
+        let mut passes = vec![];
+        for input_size in self.tta_input_sizes() {
+            let faces = self.candidates_at(image, input_size, options.score_threshold)?;
+            passes.push(select_faces(faces, &pass_options));
+
+            if let Some(flipped) = &flipped {
+                let faces = self
+                    .candidates_at(flipped, input_size, options.score_threshold)?
+                    .into_iter()
+                    .map(|face| mirror_face(face, image_width))
+                    .collect();
+                passes.push(select_faces(faces, &pass_options));
+            }
         }
+
+        Ok(fused_faces(
+            passes,
+            self.tta.fusion_iou,
+            options,
+            image.dimensions(),
+        ))
+    }
+
+    /// Размеры входа для масштабов TTA, кратные наибольшему шагу сетки.
+    fn tta_input_sizes(&self) -> Vec<(u32, u32)> {
+        let input_size = self.grid.input_size.dimensions();
+
+        let fixed = matches!(
+            self.metadata.input_shape.as_slice(),
+            [_, _, height, width] if *height > 0 && *width > 0
+        );
+        if fixed {
+            return vec![input_size];
+        }
+
+        let step = self.grid.strides.iter().max().copied().unwrap_or(1) as f32;
+        let scaled =
+            |side: u32, scale: f32| ((side as f32 * scale / step).round().max(1.) * step) as u32;
+
+        let mut sizes: Vec<(u32, u32)> = vec![];
+        for &scale in &self.tta.scales {
+            let size = (scaled(input_size.0, scale), scaled(input_size.1, scale));
+            if !sizes.contains(&size) {
+                sizes.push(size);
+            }
+        }
+
+        sizes
     }
 
-    pub fn predict(&self, image: &DynamicImage) -> Vec<DetectedFaceOutput> {
-        let session = self.load_session();
+    /// Лица одного прохода детектора до подавления пересекающихся рамок.
+    fn candidates(&self, image: &DynamicImage, threshold: f32) -> Result<Vec<DetectedFaceOutput>> {
+        self.candidates_at(image, self.grid.input_size.dimensions(), threshold)
+    }
 
-        let resized_image = resize(image, 640, 640);
+    /// Лица одного прохода детектора с размером входа `input_size`.
+    fn candidates_at(
+        &self,
+        image: &DynamicImage,
+        input_size: (u32, u32),
+        threshold: f32,
+    ) -> Result<Vec<DetectedFaceOutput>> {
+        let (outputs, letterbox) = self.run(self.input_tensor(image, input_size))?;
 
-        let image_tensor = Self::get_tensor(&resized_image.to_rgba32f());
+        candidates(&outputs, &self.head_for(input_size), threshold, &letterbox)
+    }
 
-        let outputs = session.run(inputs![image_tensor].unwrap()).unwrap();
+    /// Формат выходов с центрами якорей для входа размера `input_size`.
+    fn head_for(&self, input_size: (u32, u32)) -> Cow<'_, DetectorHead> {
+        if input_size == self.grid.input_size.dimensions() {
+            return Cow::Borrowed(&self.head);
+        }
 
-        post_processing(outputs, 0.5, image)
+        match self.tta_heads.iter().find(|(size, _)| *size == input_size) {
+            Some((_, head)) => Cow::Borrowed(head),
+            None => Cow::Owned(self.head.with_input_size(input_size)),
+        }
     }
 
-    fn get_tensor(image: &Rgba32FImage) -> ndarray::Array<f32, ndarray::Dim<[usize; 4]>> {
-        let shape = image.dimensions();
+    /// Вписывает изображение во вход размера `input_size`.
+    fn input_tensor(
+        &self,
+        image: &DynamicImage,
+        (width, height): (u32, u32),
+    ) -> (Array4<f32>, Letterbox) {
+        let (resized_image, letterbox) = resize(image, width, height, self.grid.center_padding);
 
-        let input = Array::from_shape_fn(
-            (1_usize, 3_usize, shape.0 as usize, shape.1 as usize),
-            |(_, c, i, j)| ((image[(j as _, i as _)][c] as f32) - 0.5f32) / 0.5f32,
+        (normalized_tensor(&resized_image.to_rgba32f()), letterbox)
+    }
+}
+
+impl FaceDetection for FaceDetector {
+    fn options(&self) -> &DetectionOptions {
+        &self.options
+    }
+
+    /// Находит лица с параметрами постобработки `options` вместо параметров модели.
+    ///
+    /// Если включена детекция по фрагментам, к общему проходу по уменьшенному изображению
+    /// добавляются лица из фрагментов в исходном разрешении. Лица, обрезанные границей
+    /// фрагмента, отбрасываются, а повторы из соседних фрагментов подавляются вместе
+    /// с остальными пересекающимися рамками.
+    ///
+    /// С `options.tta` вместо этого выполняется аугментация (см. [`TtaOptions`]).
+    ///
+    /// [`Predictor::predict`] выполняет только один проход по уменьшенному изображению
+    /// с параметрами модели.
+    fn detect(
+        &self,
+        image: &DynamicImage,
+        options: &DetectionOptions,
+    ) -> Result<Vec<DetectedFaceOutput>> {
+        if options.tta {
+            return self.detect_tta(image, options);
+        }
+
+        let mut faces = self.candidates(image, options.score_threshold)?;
+
+        if let Some(tiling) = &self.tiling {
+            let image_size = image.dimensions();
+
+            for tile in tiles(image_size, tiling) {
+                let crop = image.crop_imm(tile.x, tile.y, tile.width, tile.height);
+
+                faces.extend(
+                    self.candidates(&crop, options.score_threshold)?
+                        .into_iter()
+                        .filter(|face| !tile.cuts(&face.bbox, image_size))
+                        .map(|face| tile.to_image(face)),
+                );
+            }
+        }
+
+        Ok(final_faces(faces, options, image.dimensions()))
+    }
+}
+
+impl Predictor for FaceDetector {
+    type Input<'a> = &'a DynamicImage;
+    type Output = Vec<DetectedFaceOutput>;
+    type Tensor = (Array4<f32>, Letterbox);
+    type RawOutput = (Vec<DynValue>, Letterbox);
+
+    fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    /// Вписывает изображение во вход детектора. [`Letterbox`] передается
+    /// дальше для перевода координат лиц обратно в исходное изображение.
+    fn preprocess(&self, image: &DynamicImage) -> Result<(Array4<f32>, Letterbox)> {
+        Ok(self.input_tensor(image, self.grid.input_size.dimensions()))
+    }
+
+    fn run(
+        &self,
+        (tensor, letterbox): (Array4<f32>, Letterbox),
+    ) -> Result<(Vec<DynValue>, Letterbox)> {
+        Ok((self.session.run(inputs![tensor]?)?, letterbox))
+    }
+
+    fn postprocess(
+        &self,
+        _: &DynamicImage,
+        (outputs, letterbox): (Vec<DynValue>, Letterbox),
+    ) -> Result<Vec<DetectedFaceOutput>> {
+        post_processing(&outputs, &self.head, &self.options, &letterbox)
+    }
+
+    /// Проверяет параметры сетки и постобработки. Ожидаются вход `[1, 3, H, W]`
+    /// и выходы в формате, указанном в `head`.
+    fn validate(&self) -> Vec<String> {
+        let (inputs, outputs) = (&self.metadata.inputs, &self.metadata.outputs);
+        let (width, height) = self.grid.input_size.dimensions();
+
+        let mut problems = self.grid.problems();
+        if let Some(tiling) = &self.tiling {
+            problems.extend(tiling.problems());
+        }
+        problems.extend(self.tta.problems());
+        problems.extend(self.options.problems());
+        problems.extend(
+            [
+                check_count("input", inputs, 1),
+                check_tensor("input", inputs, 0, &[1, 3, height as i64, width as i64]),
+            ]
+            .into_iter()
+            .flatten(),
         );
 
-        return input;
+        problems.extend(self.head.validate(outputs));
+
+        problems
     }
 
-    fn load_session(&self) -> Session {
-        Session::builder()
-            .unwrap()
-            .with_optimization_level(GraphOptimizationLevel::Disable)
-            .unwrap()
-            .commit_from_file(&self.model_path)
-            .unwrap()
+    fn warmup(&self) -> Result<()> {
+        let (width, height) = self.grid.input_size.dimensions();
+        self.predict(&DynamicImage::new_rgb8(width, height))?;
+        Ok(())
     }
 }
@@ -0,0 +1,381 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::models::DetectedFaceOutput;
+
+const EPS: f32 = 1.0e-7;
+
+/// Наибольшее количество ячеек сетки, в которые добавляется одна рамка. Рамки крупнее
+/// сравниваются со всеми остальными попарно.
+const MAX_CELLS_PER_BOX: i64 = 64;
+
+/// Убывание оценки рамки в Soft-NMS в зависимости от ее IoU с выбранной рамкой.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoftNmsDecay {
+    /// Оценки рамок с IoU выше порога умножаются на `1 - IoU`.
+    Linear(f32),
+    /// Оценки всех пересекающихся рамок умножаются на `exp(-IoU² / sigma)`.
+    Gaussian(f32),
+}
+
+impl SoftNmsDecay {
+    fn weight(&self, overlap: f32) -> f32 {
+        match *self {
+            SoftNmsDecay::Linear(threshold) if overlap > threshold => 1. - overlap,
+            SoftNmsDecay::Linear(_) => 1.,
+            SoftNmsDecay::Gaussian(sigma) => (-overlap * overlap / sigma).exp(),
+        }
+    }
+}
+
+/// Жадное подавление пересекающихся рамок (NMS).
+///
+/// Рамки должны быть отсортированы по убыванию оценки. Рамка остается, если ее IoU
+/// с каждой из уже выбранных не превышает `max_iou`. Выбранные рамки хранятся
+/// в равномерной сетке, поэтому каждая рамка сравнивается только с соседними,
+/// и подавление масштабируется на тысячи кандидатов. Рамки с нечисловыми
+/// координатами отбрасываются.
+pub fn non_maximum_suppression(
+    mut sorted_faces: Vec<DetectedFaceOutput>,
+    max_iou: f32,
+) -> Vec<DetectedFaceOutput> {
+    sorted_faces.retain(is_finite);
+
+    let mut grid = BoxGrid::new(&sorted_faces);
+    let mut selected: Vec<DetectedFaceOutput> = vec![];
+
+    for face in sorted_faces {
+        let suppressed = grid
+            .neighbours(&face.bbox)
+            .any(|index| iou(&face.bbox, &selected[index].bbox) > max_iou);
+
+        if !suppressed {
+            grid.insert(&face.bbox, selected.len());
+            selected.push(face);
+        }
+    }
+
+    selected
+}
+
+/// Мягкое подавление пересекающихся рамок (Soft-NMS).
+///
+/// Вместо удаления оценки рамок, пересекающихся с очередной самой уверенной рамкой,
+/// уменьшаются по правилу `decay`. Рамки с оценкой ниже `min_score` отбрасываются.
+/// Близко стоящие лица на групповых фотографиях при этом сохраняются.
+///
+/// Оценки уменьшаются только у соседей выбранной рамки по сетке: у остальных рамок
+/// IoU равен нулю, и их оценки не меняются. Очередная самая уверенная рамка берется
+/// из кучи, записи с устаревшей оценкой пропускаются.
+pub fn soft_nms(
+    mut faces: Vec<DetectedFaceOutput>,
+    decay: SoftNmsDecay,
+    min_score: f32,
+) -> Vec<DetectedFaceOutput> {
+    faces.retain(|face| is_finite(face) && face.score > min_score);
+
+    let mut grid = BoxGrid::new(&faces);
+    for (index, face) in faces.iter().enumerate() {
+        grid.insert(&face.bbox, index);
+    }
+
+    let mut queue: BinaryHeap<Candidate> = faces
+        .iter()
+        .enumerate()
+        .map(|(index, face)| Candidate::new(face.score, index))
+        .collect();
+    let mut removed = vec![false; faces.len()];
+    // Номер выбора, на котором рамка уже получила уменьшение оценки
+    let mut decayed = vec![usize::MAX; faces.len()];
+    let mut selected = vec![];
+
+    while let Some(Candidate { score, index }) = queue.pop() {
+        if removed[index] || score.to_bits() != faces[index].score.to_bits() {
+            continue;
+        }
+        removed[index] = true;
+
+        let best = faces[index].bbox;
+        for neighbour in grid.neighbours(&best) {
+            if removed[neighbour] || decayed[neighbour] == selected.len() {
+                continue;
+            }
+            decayed[neighbour] = selected.len();
+
+            let face = &mut faces[neighbour];
+            let weight = decay.weight(iou(&best, &face.bbox));
+            if weight == 1. {
+                continue;
+            }
+
+            face.score *= weight;
+            if face.score > min_score {
+                queue.push(Candidate::new(face.score, neighbour));
+            } else {
+                removed[neighbour] = true;
+            }
+        }
+
+        selected.push(index);
+    }
+
+    selected
+        .into_iter()
+        .map(|index| faces[index].clone())
+        .collect()
+}
+
+/// Рамка в очереди Soft-NMS. Рамки с равной оценкой выбираются в исходном порядке.
+struct Candidate {
+    score: f32,
+    index: usize,
+}
+
+impl Candidate {
+    fn new(score: f32, index: usize) -> Self {
+        Candidate { score, index }
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+/// Взвешенное слияние рамок (WBF) одного или нескольких проходов детектора.
+///
+/// Рамки в порядке убывания оценки присоединяются к объединенной рамке, с которой
+/// у них наибольший IoU выше `iou_threshold`, иначе образуют новую. Координаты рамки
+/// и ключевых точек объединенного лица — средние с весами-оценками, оценка — средняя
+/// оценка, уменьшенная пропорционально доле проходов, не нашедших лицо.
+///
+/// Объединенные рамки хранятся в сетке и заново добавляются в нее при каждом
+/// изменении, поэтому рамка сравнивается только с соседними объединенными рамками.
+/// Для каждой объединенной рамки хранятся накопленные взвешенные суммы координат.
+pub fn weighted_box_fusion(
+    passes: Vec<Vec<DetectedFaceOutput>>,
+    iou_threshold: f32,
+) -> Vec<DetectedFaceOutput> {
+    let pass_count = passes.len().max(1);
+
+    let mut faces: Vec<DetectedFaceOutput> = passes.into_iter().flatten().collect();
+    faces.retain(is_finite);
+    faces.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut grid = BoxGrid::new(&faces);
+    let mut clusters: Vec<Cluster> = vec![];
+    for face in faces {
+        let best = grid
+            .neighbours(&face.bbox)
+            .map(|index| (index, iou(&clusters[index].fused.bbox, &face.bbox)))
+            .filter(|(_, overlap)| *overlap > iou_threshold)
+            .max_by(|(a, overlap_a), (b, overlap_b)| overlap_a.total_cmp(overlap_b).then(a.cmp(b)));
+
+        match best {
+            Some((index, _)) => {
+                let cluster = &mut clusters[index];
+                cluster.add(&face);
+                grid.insert(&cluster.fused.bbox, index);
+            }
+            None => {
+                grid.insert(&face.bbox, clusters.len());
+                clusters.push(Cluster::new(face));
+            }
+        }
+    }
+
+    let mut fused: Vec<DetectedFaceOutput> = clusters
+        .into_iter()
+        .map(|cluster| {
+            let mut fused = cluster.fused;
+            fused.score *= cluster.count.min(pass_count) as f32 / pass_count as f32;
+            fused
+        })
+        .collect();
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    fused
+}
+
+/// Рамки одного лица при слиянии: суммы оценок и координат с весами-оценками
+/// и объединенное лицо.
+///
+/// Ключевые точки усредняются по рамкам, у которых они есть.
+struct Cluster {
+    fused: DetectedFaceOutput,
+    count: usize,
+    score: f32,
+    bbox: [f32; 4],
+    landmarks: Option<(f32, [(f32, f32); 5])>,
+}
+
+impl Cluster {
+    fn new(face: DetectedFaceOutput) -> Self {
+        let mut cluster = Cluster {
+            fused: face.clone(),
+            count: 0,
+            score: 0.,
+            bbox: [0.; 4],
+            landmarks: None,
+        };
+        cluster.accumulate(&face);
+        cluster
+    }
+
+    fn add(&mut self, face: &DetectedFaceOutput) {
+        self.accumulate(face);
+
+        let total = self.score + EPS;
+        let landmarks = self.landmarks.map(|(score, landmarks)| {
+            let total = score + EPS;
+            landmarks.map(|(x, y)| (x / total, y / total))
+        });
+
+        self.fused = DetectedFaceOutput::new(
+            self.score / self.count as f32,
+            self.bbox.map(|coordinate| coordinate / total),
+            landmarks,
+        );
+    }
+
+    fn accumulate(&mut self, face: &DetectedFaceOutput) {
+        self.count += 1;
+        self.score += face.score;
+        for (sum, coordinate) in self.bbox.iter_mut().zip(face.bbox) {
+            *sum += coordinate * face.score;
+        }
+
+        if let Some(landmarks) = face.landmarks {
+            let (score, sums) = self.landmarks.get_or_insert((0., [(0., 0.); 5]));
+            *score += face.score;
+            for (sum, (x, y)) in sums.iter_mut().zip(landmarks) {
+                sum.0 += x * face.score;
+                sum.1 += y * face.score;
+            }
+        }
+    }
+}
+
+/// Оценка и координаты рамки — конечные числа.
+pub fn is_finite(face: &DetectedFaceOutput) -> bool {
+    face.score.is_finite() && face.bbox.iter().all(|coordinate| coordinate.is_finite())
+}
+
+/// Равномерная сетка для поиска пересекающихся рамок.
+///
+/// Рамка добавляется во все ячейки, которые она покрывает, поэтому пересекающиеся
+/// рамки всегда имеют общую ячейку. Размер ячейки — средний размер рамок.
+/// Рамки, покрывающие больше [`MAX_CELLS_PER_BOX`] ячеек, хранятся отдельно
+/// и считаются соседями любой рамки.
+struct BoxGrid {
+    cell: f32,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    oversized: Vec<usize>,
+    count: usize,
+}
+
+impl BoxGrid {
+    fn new(faces: &[DetectedFaceOutput]) -> Self {
+        let sizes: f32 = faces
+            .iter()
+            .map(|face| f32::max(face.bbox[2] - face.bbox[0], face.bbox[3] - face.bbox[1]))
+            .filter(|size| size.is_finite() && *size > 0.)
+            .sum();
+
+        BoxGrid {
+            cell: (sizes / faces.len().max(1) as f32).max(1.),
+            cells: HashMap::new(),
+            oversized: vec![],
+            count: 0,
+        }
+    }
+
+    fn insert(&mut self, bbox: &[f32; 4], index: usize) {
+        self.count = self.count.max(index + 1);
+
+        match self.keys(bbox) {
+            Some(keys) => {
+                for key in keys {
+                    self.cells.entry(key).or_default().push(index);
+                }
+            }
+            None => self.oversized.push(index),
+        }
+    }
+
+    /// Номера рамок из ячеек, которые покрывает `bbox`, и крупных рамок; для крупной
+    /// `bbox` — номера всех рамок. Номер может повторяться.
+    fn neighbours<'a>(&'a self, bbox: &[f32; 4]) -> impl Iterator<Item = usize> + 'a {
+        let keys = self.keys(bbox);
+        let everything = match keys {
+            Some(_) => 0..0,
+            None => 0..self.count,
+        };
+
+        keys.into_iter()
+            .flatten()
+            .filter_map(|key| self.cells.get(&key))
+            .flatten()
+            .copied()
+            .chain(self.oversized.iter().copied())
+            .chain(everything)
+    }
+
+    /// Ячейки, которые покрывает `bbox`, или `None`, если их больше [`MAX_CELLS_PER_BOX`].
+    fn keys(&self, bbox: &[f32; 4]) -> Option<impl Iterator<Item = (i64, i64)>> {
+        let cell = |value: f32| (value / self.cell).floor() as i64;
+        let (x1, x2) = (cell(bbox[0].min(bbox[2])), cell(bbox[0].max(bbox[2])));
+        let (y1, y2) = (cell(bbox[1].min(bbox[3])), cell(bbox[1].max(bbox[3])));
+
+        let cells = (x2.saturating_sub(x1).saturating_add(1))
+            .saturating_mul(y2.saturating_sub(y1).saturating_add(1));
+        if cells > MAX_CELLS_PER_BOX {
+            return None;
+        }
+
+        Some((y1..=y2).flat_map(move |y| (x1..=x2).map(move |x| (x, y))))
+    }
+}
+
+/// Вычисление показателя пересечения над объединением для двух ограничивающих прямоугольников.
+pub fn iou(bbox_a: &[f32; 4], bbox_b: &[f32; 4]) -> f32 {
+    let overlap_box: [f32; 4] = [
+        f32::max(bbox_a[0], bbox_b[0]),
+        f32::max(bbox_a[1], bbox_b[1]),
+        f32::min(bbox_a[2], bbox_b[2]),
+        f32::min(bbox_a[3], bbox_b[3]),
+    ];
+
+    let overlap_area = bbox_area(&overlap_box);
+
+    overlap_area / (bbox_area(bbox_a) + bbox_area(bbox_b) - overlap_area + EPS)
+}
+
+fn bbox_area(bbox: &[f32; 4]) -> f32 {
+    let width = bbox[2] - bbox[0];
+    let height = bbox[3] - bbox[1];
+    if width < 0.0 || height < 0.0 {
+        return 0.0;
+    }
+
+    width * height
+}
@@ -0,0 +1,79 @@
+use crate::{config::TilingOptions, models::DetectedFaceOutput};
+
+/// Фрагмент изображения.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    /// Переводит координаты лица из фрагмента в координаты изображения.
+    pub fn to_image(&self, mut face: DetectedFaceOutput) -> DetectedFaceOutput {
+        let (dx, dy) = (self.x as f32, self.y as f32);
+
+        face.bbox = [
+            face.bbox[0] + dx,
+            face.bbox[1] + dy,
+            face.bbox[2] + dx,
+            face.bbox[3] + dy,
+        ];
+        face.landmarks = face
+            .landmarks
+            .map(|landmarks| landmarks.map(|(x, y)| (x + dx, y + dy)));
+
+        face
+    }
+
+    /// Касается ли рамка `bbox` (в координатах фрагмента) границы фрагмента внутри
+    /// изображения `image_size`. Такое лицо обрезано фрагментом и целиком попадает
+    /// в соседний фрагмент или в общий проход.
+    pub fn cuts(&self, bbox: &[f32; 4], (image_width, image_height): (u32, u32)) -> bool {
+        let (width, height) = (self.width as f32, self.height as f32);
+
+        (self.x > 0 && bbox[0] <= 1.)
+            || (self.y > 0 && bbox[1] <= 1.)
+            || (self.x + self.width < image_width && bbox[2] >= width - 1.)
+            || (self.y + self.height < image_height && bbox[3] >= height - 1.)
+    }
+}
+
+/// Разбивает изображение `image_size` на перекрывающиеся фрагменты.
+///
+/// Последний фрагмент в ряду прижимается к краю изображения. Если изображение
+/// помещается в один фрагмент, фрагменты не нужны и список пуст.
+pub fn tiles((width, height): (u32, u32), options: &TilingOptions) -> Vec<Tile> {
+    if width <= options.tile_size && height <= options.tile_size {
+        return vec![];
+    }
+
+    let columns = positions(width, options);
+    let rows = positions(height, options);
+
+    rows.iter()
+        .flat_map(|&y| {
+            columns.iter().map(move |&x| Tile {
+                x,
+                y,
+                width: options.tile_size.min(width),
+                height: options.tile_size.min(height),
+            })
+        })
+        .collect()
+}
+
+/// Начала фрагментов вдоль стороны длины `length`.
+fn positions(length: u32, options: &TilingOptions) -> Vec<u32> {
+    if length <= options.tile_size {
+        return vec![0];
+    }
+
+    let step = (options.tile_size - options.overlap).max(1) as usize;
+    let last = length - options.tile_size;
+
+    let mut positions: Vec<u32> = (0..last).step_by(step).collect();
+    positions.push(last);
+    positions
+}
@@ -3,6 +3,12 @@ mod recognition;
 mod swap;
 mod transforms;
 
-pub use detection::predictor::FaceDetector;
+pub use detection::{
+    head::{anchor_center_array, anchor_centers, decode_anchors, DetectorHead},
+    post_processing::{clip_to_image, final_faces, fused_faces, mirror_face},
+    predictor::FaceDetector,
+    suppression::{iou, non_maximum_suppression, soft_nms, weighted_box_fusion, SoftNmsDecay},
+    tiling::{tiles, Tile},
+};
 pub use recognition::predictor::FaceRecognizer;
-pub use transforms::umeyama;
+pub use transforms::{aligned_face_tensor, resize, umeyama, Letterbox};
@@ -1,67 +1,207 @@
-use image::{DynamicImage, Rgba32FImage};
-use ndarray::Array;
-use ort::{inputs, GraphOptimizationLevel, Session};
+use std::sync::Arc;
 
-use crate::{ml::facial_processing::transforms::crop_face, models::DetectedFaceOutput};
+use image::DynamicImage;
+use ndarray::Array4;
+use ort::inputs;
+
+use crate::{
+    config::{BatchingOptions, ModelData},
+    error::{Error, Result},
+    ml::{
+        batching::{split_rows, stack_batch, Batcher},
+        facial_processing::transforms::aligned_face_tensor,
+        fingerprint::ModelFingerprint,
+        inference_pool::InferencePool,
+        predictor::{ModelMetadata, Predictor},
+        session_pool::{PoolStatus, SessionPool},
+        tasks::FaceEmbedding,
+        validation::{batch_limit, check_batch_dimension, check_count, check_tensor},
+    },
+    models::DetectedFaceOutput,
+};
 
 #[derive(Debug, Clone)]
 pub struct FaceRecognizer {
-    pub model_path: String,
-    pub model_name: String,
+    metadata: ModelMetadata,
+    max_batch_size: usize,
+    session: Arc<SessionPool>,
+    batcher: Option<Arc<Batcher<Array4<f32>, Vec<f32>>>>,
 }
 
 impl FaceRecognizer {
-    pub fn new(path: String, name: String) -> Self {
-        FaceRecognizer {
-            model_path: path,
-            model_name: name,
-        }
+    pub fn new(path: String, name: String) -> Result<Self> {
+        Self::from_config(ModelData::new(path, name))
     }
 
-    pub fn predict(
+    pub fn from_config(config: ModelData) -> Result<Self> {
+        let fingerprint = ModelFingerprint::verify(&config)?;
+        let session = Arc::new(SessionPool::from_config(&config)?);
+        let max_batch_size = batch_limit(session.input_shape(), config.max_batch_size);
+
+        let batcher = config.batching.as_ref().map(|options| {
+            let options = BatchingOptions {
+                max_batch_size: batch_limit(session.input_shape(), options.max_batch_size),
+                ..options.clone()
+            };
+            let session = session.clone();
+            Arc::new(Batcher::new(&config.model_name, &options, move |crops| {
+                Self::run_batch(&session, &crops)
+            }))
+        });
+
+        Ok(FaceRecognizer {
+            max_batch_size,
+            metadata: ModelMetadata::new(config, &session, fingerprint),
+            session,
+            batcher,
+        })
+    }
+
+    pub fn pool_status(&self) -> PoolStatus {
+        self.session.status()
+    }
+
+    /// Выравнивает каждое лицо отдельно: лицо, которое невозможно выровнять,
+    /// получает ошибку и не мешает обработке остальных.
+    pub fn align_faces(
         &self,
-        raw_image: &DynamicImage,
-        faces: &Vec<DetectedFaceOutput>,
-    ) -> Vec<[f32; 512]> {
-        let image = raw_image.to_rgba32f();
+        image: &DynamicImage,
+        faces: &[DetectedFaceOutput],
+    ) -> Vec<Result<Array4<f32>>> {
+        let image = image.to_rgba32f();
 
         faces
             .iter()
-            .map(|face| crop_face(&image, &face.landmarks, 112))
-            .map(|crop| Self::get_tensor(&crop))
-            .map(|tensor| self.calculate_embedding(tensor))
+            .map(|face| aligned_face_tensor(&image, face, 112))
             .collect()
     }
 
-    fn calculate_embedding(
+    /// Вычисляет эмбеддинги для батча выровненных лиц `[B, 3, 112, 112]`.
+    fn run_batch(session: &SessionPool, crops: &[Array4<f32>]) -> Result<Vec<Vec<f32>>> {
+        let batch = stack_batch(crops)?;
+        let outputs = session.run(inputs![batch]?)?;
+
+        let embeddings = outputs
+            .first()
+            .ok_or_else(|| Error::ModelOutput("recognizer has no outputs".into()))?
+            .try_extract_tensor::<f32>()?;
+
+        split_rows(embeddings, crops.len())
+    }
+}
+
+impl FaceEmbedding for FaceRecognizer {
+    /// Лица выравниваются в пуле `inference` (см. [`Predictor::preprocess`]). С батчингом
+    /// выровненные лица передаются планировщику батчей, и результат ожидается асинхронно,
+    /// не занимая потоки пула; иначе модель запускается в пуле (см. [`Predictor::run`]).
+    async fn embed_faces(
         &self,
-        image: ndarray::Array<f32, ndarray::Dim<[usize; 4]>>,
-    ) -> [f32; 512] {
-        let session = self.load_session();
-        let outputs = session.run(inputs![image].unwrap()).unwrap();
+        inference: &InferencePool,
+        image: DynamicImage,
+        faces: Vec<DetectedFaceOutput>,
+    ) -> Result<Vec<Result<Vec<f32>>>> {
+        // Лица, которые невозможно выровнять, не попадают в батч
+        let (image, aligned, crops, alignment) = {
+            let model = self.clone();
+            inference
+                .spawn(move || {
+                    let mut aligned = Vec::with_capacity(faces.len());
+                    let mut crops = Vec::with_capacity(faces.len());
+                    let alignment: Vec<Result<()>> = model
+                        .align_faces(&image, &faces)
+                        .into_iter()
+                        .zip(faces)
+                        .map(|(crop, face)| {
+                            crop.map(|crop| {
+                                crops.push(crop);
+                                aligned.push(face);
+                            })
+                        })
+                        .collect();
 
-        let embedding = outputs[0].try_extract_tensor().unwrap();
+                    Ok((image, aligned, crops, alignment))
+                })
+                .await?
+        };
 
-        return embedding.as_slice().unwrap().try_into().unwrap();
+        let embeddings = match &self.batcher {
+            Some(batcher) if !crops.is_empty() => batcher.submit_many(crops).await?,
+            _ => {
+                let model = self.clone();
+                inference.spawn(move || model.run(crops)).await?
+            }
+        };
+        let mut embeddings = self
+            .postprocess((&image, &aligned), embeddings)?
+            .into_iter();
+
+        Ok(alignment
+            .into_iter()
+            .map(|aligned| {
+                aligned.and_then(|()| {
+                    embeddings
+                        .next()
+                        .ok_or_else(|| Error::ModelOutput("face embeddings are missing".into()))
+                })
+            })
+            .collect())
     }
+}
 
-    fn get_tensor(image: &Rgba32FImage) -> ndarray::Array<f32, ndarray::Dim<[usize; 4]>> {
-        let shape = image.dimensions();
+impl Predictor for FaceRecognizer {
+    type Input<'a> = (&'a DynamicImage, &'a [DetectedFaceOutput]);
+    type Output = Vec<Vec<f32>>;
+    type Tensor = Vec<Array4<f32>>;
+    type RawOutput = Vec<Vec<f32>>;
+
+    fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    fn preprocess(
+        &self,
+        (raw_image, faces): (&DynamicImage, &[DetectedFaceOutput]),
+    ) -> Result<Vec<Array4<f32>>> {
+        self.align_faces(raw_image, faces).into_iter().collect()
+    }
+
+    /// Все лица изображения обрабатываются одним запуском модели
+    /// (или несколькими, если лиц больше `max_batch_size`) без планировщика
+    /// батчей (см. [`FaceEmbedding::embed_faces`]).
+    fn run(&self, crops: Vec<Array4<f32>>) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(crops.len());
+        for chunk in crops.chunks(self.max_batch_size) {
+            embeddings.extend(Self::run_batch(&self.session, chunk)?);
+        }
+        Ok(embeddings)
+    }
+
+    fn postprocess(
+        &self,
+        _: (&DynamicImage, &[DetectedFaceOutput]),
+        embeddings: Vec<Vec<f32>>,
+    ) -> Result<Vec<Vec<f32>>> {
+        Ok(embeddings)
+    }
 
-        let input = Array::from_shape_fn(
-            (1_usize, 3_usize, shape.0 as usize, shape.1 as usize),
-            |(_, c, i, j)| ((image[(j as _, i as _)][c] as f32) - 0.5f32) / 0.5f32,
-        );
+    /// Ожидаются вход `[B, 3, 112, 112]` с динамическим размером батча или `B = 1`
+    /// и эмбеддинг `[B, 512]`. При `B = 1` лица подаются в модель по одному.
+    fn validate(&self) -> Vec<String> {
+        let (inputs, outputs) = (&self.metadata.inputs, &self.metadata.outputs);
 
-        return input;
+        [
+            check_count("input", inputs, 1),
+            check_tensor("input", inputs, 0, &[-1, 3, 112, 112]),
+            check_batch_dimension(&self.metadata.input_shape),
+            check_tensor("output", outputs, 0, &[-1, 512]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
     }
 
-    fn load_session(&self) -> Session {
-        Session::builder()
-            .unwrap()
-            .with_optimization_level(GraphOptimizationLevel::Disable)
-            .unwrap()
-            .commit_from_file(&self.model_path)
-            .unwrap()
+    fn warmup(&self) -> Result<()> {
+        Self::run_batch(&self.session, &[Array4::zeros((1, 3, 112, 112))])?;
+        Ok(())
     }
 }
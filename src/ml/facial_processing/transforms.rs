@@ -1,11 +1,23 @@
 use std::ops::Mul;
 
-use image::{DynamicImage, Rgba32FImage};
+use image::{DynamicImage, GenericImageView, Rgba32FImage};
 use nalgebra::Matrix3;
 use nalgebra::{ArrayStorage, Matrix1x2, Matrix2, Matrix2x1, Matrix3x1};
-
-pub fn warp_into(input: &Rgba32FImage, matrix: Matrix3<f32>, output: &mut Rgba32FImage) {
-    let inverse = matrix.try_inverse().unwrap();
+use ndarray::{Array, Array4};
+
+use crate::{
+    error::{Error, Result},
+    models::DetectedFaceOutput,
+};
+
+pub fn warp_into(
+    input: &Rgba32FImage,
+    matrix: Matrix3<f32>,
+    output: &mut Rgba32FImage,
+) -> Result<()> {
+    let inverse = matrix
+        .try_inverse()
+        .ok_or_else(|| Error::FaceAlignment("transformation matrix is not invertible".into()))?;
 
     let in_width = input.width();
     let in_height = input.height();
@@ -33,11 +45,16 @@ pub fn warp_into(input: &Rgba32FImage, matrix: Matrix3<f32>, output: &mut Rgba32
             }
         }
     }
+
+    Ok(())
 }
 
 /// Алгоритм `Кабша-Умеямы` - это метод нахождения оптимального перемещения, поворота
 /// и масштабирования, который выравнивает два набора точек с минимальным среднеквадратичным отклонением (RMSD).
-pub fn umeyama<const R: usize>(src: &[(f32, f32); R], dst: &[(f32, f32); R]) -> Matrix3<f32> {
+pub fn umeyama<const R: usize>(
+    src: &[(f32, f32); R],
+    dst: &[(f32, f32); R],
+) -> Result<Matrix3<f32>> {
     let src_x_sum: f32 = src.iter().map(|v| v.0).sum();
     let src_x_mean = src_x_sum / (R as f32);
 
@@ -75,7 +92,9 @@ pub fn umeyama<const R: usize>(src: &[(f32, f32); R], dst: &[(f32, f32); R]) ->
     let rank = a.rank(0.00001f32);
 
     if rank == 0 {
-        panic!("Matrix rank is 0.");
+        return Err(Error::FaceAlignment(
+            "landmarks covariance matrix rank is 0".into(),
+        ));
     } else if rank == 2 - 1 {
         if u.determinant() * v.determinant() > 0.0 {
             u.mul_to(&v, &mut t);
@@ -121,39 +140,162 @@ pub fn umeyama<const R: usize>(src: &[(f32, f32); R], dst: &[(f32, f32); R]) ->
     let m12 = m00x22.m12;
     let m22 = m00x22.m22;
 
-    let tt = Matrix3::<f32>::new(m11, m12, m13, m21, m22, m23, 0f32, 0f32, 1f32);
+    Ok(Matrix3::<f32>::new(
+        m11, m12, m13, m21, m22, m23, 0f32, 0f32, 1f32,
+    ))
+}
 
-    return tt;
+/// Вписывание изображения во вход модели с сохранением соотношения сторон.
+///
+/// Изображение размера `source_size` масштабируется в `scale` раз по каждой оси
+/// и сдвигается на `padding` пикселей; остальная часть входа заполняется черным.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Letterbox {
+    pub scale: (f32, f32),
+    pub padding: (f32, f32),
+    pub source_size: (u32, u32),
 }
 
-/// Изменяет размер изображения с сохранением соотношения сторон,
-/// компенсирует широту или высоту изображения черными пикселями.
-/// На выходе получаем изображение `width`*`height`
-pub fn resize(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+impl Letterbox {
+    /// Вписывает изображение `source_size` во вход `target_size`. Если `centered`,
+    /// изображение располагается по центру входа, иначе — в левом верхнем углу.
+    pub fn new(source_size: (u32, u32), target_size: (u32, u32), centered: bool) -> Self {
+        let (source_width, source_height) = source_size;
+        let (target_width, target_height) = target_size;
+
+        let ratio = f32::min(
+            target_width as f32 / source_width as f32,
+            target_height as f32 / source_height as f32,
+        );
+        let (width, height) = Self::fitted(source_size, target_size, ratio);
+
+        let padding = match centered {
+            true => ((target_width - width) / 2, (target_height - height) / 2),
+            false => (0, 0),
+        };
+
+        Letterbox {
+            scale: (
+                width as f32 / source_width as f32,
+                height as f32 / source_height as f32,
+            ),
+            padding: (padding.0 as f32, padding.1 as f32),
+            source_size,
+        }
+    }
+
+    /// Размер изображения после масштабирования (не меньше 1 пикселя по каждой оси).
+    pub fn resized_size(&self) -> (u32, u32) {
+        let (width, height) = self.source_size;
+
+        (
+            ((width as f32 * self.scale.0).round() as u32).max(1),
+            ((height as f32 * self.scale.1).round() as u32).max(1),
+        )
+    }
+
+    /// Переводит точку исходного изображения в координаты входа модели.
+    pub fn forward(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            x * self.scale.0 + self.padding.0,
+            y * self.scale.1 + self.padding.1,
+        )
+    }
+
+    /// Переводит точку входа модели в координаты исходного изображения.
+    pub fn inverse(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (
+            (x - self.padding.0) / self.scale.0,
+            (y - self.padding.1) / self.scale.1,
+        )
+    }
+
+    /// Переводит рамку `[x1, y1, x2, y2]` входа модели в координаты исходного изображения.
+    pub fn inverse_bbox(&self, [x1, y1, x2, y2]: [f32; 4]) -> [f32; 4] {
+        let (x1, y1) = self.inverse((x1, y1));
+        let (x2, y2) = self.inverse((x2, y2));
+
+        [x1, y1, x2, y2]
+    }
+
+    fn fitted(
+        (source_width, source_height): (u32, u32),
+        (target_width, target_height): (u32, u32),
+        ratio: f32,
+    ) -> (u32, u32) {
+        (
+            ((source_width as f32 * ratio).round() as u32).clamp(1, target_width),
+            ((source_height as f32 * ratio).round() as u32).clamp(1, target_height),
+        )
+    }
+}
+
+/// Вписывает изображение во вход `width`*`height` с сохранением соотношения сторон,
+/// заполняя свободную часть черными пикселями.
+///
+/// Возвращает полученное изображение и [`Letterbox`] для обратного перевода координат.
+pub fn resize(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    centered: bool,
+) -> (DynamicImage, Letterbox) {
+    let letterbox = Letterbox::new(image.dimensions(), (width, height), centered);
+    let (resized_width, resized_height) = letterbox.resized_size();
+
     let input = image
-        .resize(width, height, image::imageops::FilterType::Triangle)
+        .resize_exact(
+            resized_width,
+            resized_height,
+            image::imageops::FilterType::Triangle,
+        )
         .to_rgba32f();
 
-    let mut output = Rgba32FImage::new(width, height);
+    let mut output = Rgba32FImage::from_pixel(width, height, image::Rgba([0., 0., 0., 1.]));
+    image::imageops::replace(
+        &mut output,
+        &input,
+        letterbox.padding.0 as i64,
+        letterbox.padding.1 as i64,
+    );
 
-    for out_row in 0..width {
-        for out_col in 0..height {
-            match input.get_pixel_checked(out_row as _, out_col as _) {
-                Some(pixel) => output.put_pixel(out_row, out_col, *pixel),
-                None => output.put_pixel(out_row, out_col, image::Rgba([0., 0., 0., 1.])),
-            }
-        }
-    }
+    (DynamicImage::from(output), letterbox)
+}
 
-    DynamicImage::from(output)
+/// Преобразует изображение в тензор `[1, 3, H, W]` со значениями в диапазоне `[-1, 1]`.
+pub fn normalized_tensor(image: &Rgba32FImage) -> Array4<f32> {
+    let (width, height) = image.dimensions();
+
+    Array::from_shape_fn(
+        (1_usize, 3_usize, height as usize, width as usize),
+        |(_, c, i, j)| (image[(j as _, i as _)][c] - 0.5f32) / 0.5f32,
+    )
 }
 
-pub fn crop_face(image: &Rgba32FImage, landmarks: &[(f32, f32); 5], size: u32) -> Rgba32FImage {
-    let m = umeyama(&landmarks, &ARCFACE_DST);
+pub fn crop_face(
+    image: &Rgba32FImage,
+    landmarks: &[(f32, f32); 5],
+    size: u32,
+) -> Result<Rgba32FImage> {
+    let m = umeyama(landmarks, &ARCFACE_DST)?;
 
     let mut output = Rgba32FImage::new(size, size);
-    warp_into(image, m, &mut output);
-    output
+    warp_into(image, m, &mut output)?;
+    Ok(output)
+}
+
+/// Выравнивает лицо `face` по ключевым точкам и преобразует его в тензор `[1, 3, size, size]`.
+pub fn aligned_face_tensor(
+    image: &Rgba32FImage,
+    face: &DetectedFaceOutput,
+    size: u32,
+) -> Result<Array4<f32>> {
+    let landmarks = face
+        .landmarks
+        .as_ref()
+        .ok_or_else(|| Error::FaceAlignment("detector did not predict face landmarks".into()))?;
+
+    Ok(normalized_tensor(&crop_face(image, landmarks, size)?))
 }
 
 const ARCFACE_DST: [(f32, f32); 5] = [
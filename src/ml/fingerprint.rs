@@ -0,0 +1,57 @@
+use std::{fs::File, io};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+use crate::{
+    config::ModelData,
+    error::{Error, Result},
+};
+
+/// Отпечаток модели, которой получен эмбеддинг.
+///
+/// Сохраняется вместе с векторами, чтобы не сравнивать эмбеддинги разных моделей.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ModelFingerprint {
+    pub model_name: String,
+    /// SHA-256 файла модели в шестнадцатеричном виде.
+    #[schema(example = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")]
+    pub sha256: String,
+}
+
+impl ModelFingerprint {
+    /// Вычисляет хеш файла модели и сверяет его с `sha256` из конфигурации, если он указан.
+    pub fn verify(config: &ModelData) -> Result<Self> {
+        let actual = file_sha256(&config.model_path)?;
+
+        if let Some(expected) = &config.sha256 {
+            if !expected.eq_ignore_ascii_case(&actual) {
+                return Err(Error::ModelIntegrity {
+                    path: config.model_path.clone(),
+                    expected: expected.to_lowercase(),
+                    actual,
+                });
+            }
+        }
+
+        Ok(ModelFingerprint {
+            model_name: config.model_name.clone(),
+            sha256: actual,
+        })
+    }
+}
+
+/// SHA-256 файла в шестнадцатеричном виде.
+pub fn file_sha256(path: &str) -> Result<String> {
+    let read_error = |err: io::Error| Error::ModelLoad {
+        path: path.to_string(),
+        source: err.into(),
+    };
+
+    let mut file = File::open(path).map_err(read_error)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(read_error)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
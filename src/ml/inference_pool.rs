@@ -0,0 +1,55 @@
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use tokio::sync::oneshot;
+
+use crate::error::{Error, Result};
+
+/// Пул потоков для ресурсоемких вычислений (декодирование изображений и инференс).
+///
+/// Обработчики запросов передают работу в этот пул и ожидают результат асинхронно,
+/// поэтому потоки `tokio` не блокируются на время работы моделей.
+#[derive(Debug, Clone)]
+pub struct InferencePool {
+    pool: Arc<ThreadPool>,
+}
+
+impl InferencePool {
+    /// Создает пул из `num_threads` потоков. Если значение не указано,
+    /// количество потоков равно количеству ядер процессора.
+    pub fn new(num_threads: Option<usize>) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads.unwrap_or(0))
+            .thread_name(|index| format!("inference-{index}"))
+            .build()
+            .unwrap();
+
+        InferencePool {
+            pool: Arc::new(pool),
+        }
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Выполняет `job` в пуле и возвращает его результат.
+    pub async fn spawn<T, F>(&self, job: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+
+        self.pool.spawn(move || {
+            let result =
+                catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|_| Err(Error::WorkerPanicked));
+            let _ = sender.send(result);
+        });
+
+        receiver.await.map_err(|_| Error::WorkerPanicked)?
+    }
+}
@@ -1,2 +1,16 @@
+pub mod batching;
+#[cfg(feature = "face")]
 pub mod facial_processing;
+pub mod fingerprint;
+pub mod inference_pool;
+pub mod predictor;
+pub mod registry;
+#[cfg(feature = "search")]
 pub mod search;
+pub mod session_pool;
+pub mod tasks;
+pub mod validation;
+
+pub use fingerprint::ModelFingerprint;
+pub use predictor::{ModelMetadata, Predictor, TensorInfo};
+pub use tasks::{FaceDetection, FaceEmbedding, ImageEmbedding, TextEmbedding};
@@ -0,0 +1,82 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config::ModelData,
+    error::Result,
+    ml::{fingerprint::ModelFingerprint, session_pool::SessionPool},
+};
+
+/// Общий интерфейс моделей: предобработка → запуск модели → постобработка.
+///
+/// Реализации определяют отдельные шаги, а `predict` связывает их вместе.
+/// Обработчики и тесты, написанные через этот трейт, могут работать
+/// с любой моделью, в том числе с моками без ONNX Runtime.
+pub trait Predictor: Send + Sync {
+    /// Входные данные модели: ссылки на данные, которые копируются в шаги модели.
+    type Input<'a>: Copy;
+    /// Результат работы модели после постобработки.
+    type Output;
+    /// Данные после предобработки, которые передаются модели.
+    type Tensor;
+    /// Выход модели до постобработки.
+    type RawOutput;
+
+    fn metadata(&self) -> &ModelMetadata;
+
+    fn preprocess(&self, input: Self::Input<'_>) -> Result<Self::Tensor>;
+
+    fn run(&self, tensor: Self::Tensor) -> Result<Self::RawOutput>;
+
+    fn postprocess(&self, input: Self::Input<'_>, raw: Self::RawOutput) -> Result<Self::Output>;
+
+    fn predict(&self, input: Self::Input<'_>) -> Result<Self::Output> {
+        let tensor = self.preprocess(input)?;
+        let raw = self.run(tensor)?;
+        self.postprocess(input, raw)
+    }
+
+    /// Проверяет, что входы и выходы модели соответствуют ожиданиям кода,
+    /// и возвращает список найденных проблем.
+    fn validate(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Прогревочный запуск модели на синтетических данных.
+    fn warmup(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Описание загруженной модели.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ModelMetadata {
+    pub model_name: String,
+    pub model_path: String,
+    /// Форма первого входа модели. Динамические размерности равны `-1`.
+    pub input_shape: Vec<i64>,
+    pub inputs: Vec<TensorInfo>,
+    pub outputs: Vec<TensorInfo>,
+    pub fingerprint: ModelFingerprint,
+}
+
+/// Имя и форма входа или выхода модели.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TensorInfo {
+    pub name: String,
+    /// Динамические размерности равны `-1`.
+    pub shape: Vec<i64>,
+}
+
+impl ModelMetadata {
+    pub fn new(config: ModelData, session: &SessionPool, fingerprint: ModelFingerprint) -> Self {
+        ModelMetadata {
+            model_name: config.model_name,
+            model_path: config.model_path,
+            input_shape: session.input_shape().to_vec(),
+            inputs: session.inputs().to_vec(),
+            outputs: session.outputs().to_vec(),
+            fingerprint,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    config::{ModelData, ModelSet},
+    error::{Error, Result},
+};
+
+/// Именованные модели одной задачи, одна из которых используется по умолчанию.
+///
+/// Набор моделей можно атомарно заменить через [`ModelRegistry::apply`]:
+/// запросы, уже получившие модель, завершаются на старой версии.
+#[derive(Debug)]
+pub struct ModelRegistry<P> {
+    state: RwLock<Arc<Models<P>>>,
+}
+
+#[derive(Debug)]
+struct Models<P> {
+    default: String,
+    models: BTreeMap<String, (ModelData, P)>,
+}
+
+/// Новый набор моделей задачи, подготовленный для замены текущего.
+#[derive(Debug)]
+pub struct RegistryUpdate<P> {
+    models: Models<P>,
+    /// Имена загруженных заново моделей.
+    pub changed: Vec<String>,
+}
+
+impl<P: Clone> ModelRegistry<P> {
+    /// Загружает все модели задачи `task` через `load`.
+    pub fn from_config(
+        task: &str,
+        config: &ModelSet,
+        load: impl Fn(ModelData) -> Result<P>,
+    ) -> Result<Self> {
+        let update = Self::load_update(task, config, |_, model| Ok((load(model.clone())?, true)))?;
+
+        Ok(ModelRegistry {
+            state: RwLock::new(Arc::new(update.models)),
+        })
+    }
+
+    /// Возвращает модель с именем `name` или модель по умолчанию.
+    pub fn get(&self, name: Option<&str>) -> Result<P> {
+        let state = self.snapshot();
+        let name = name.unwrap_or(&state.default);
+
+        state
+            .models
+            .get(name)
+            .map(|(_, model)| model.clone())
+            .ok_or_else(|| Error::UnknownModel(name.to_string()))
+    }
+
+    pub fn default_name(&self) -> String {
+        self.snapshot().default.clone()
+    }
+
+    /// Текущие модели вместе с их именами.
+    pub fn models(&self) -> Vec<(String, P)> {
+        self.snapshot()
+            .models
+            .iter()
+            .map(|(name, (_, model))| (name.clone(), model.clone()))
+            .collect()
+    }
+
+    /// Подготавливает новый набор моделей по конфигурации. Заново загружаются
+    /// только новые модели и модели с измененными параметрами, остальные переиспользуются.
+    pub fn prepare(
+        &self,
+        task: &str,
+        config: &ModelSet,
+        load: impl Fn(ModelData) -> Result<P>,
+    ) -> Result<RegistryUpdate<P>> {
+        let current = self.snapshot();
+
+        Self::load_update(task, config, |name, model| match current.models.get(name) {
+            Some((data, predictor)) if data.same_model(model) => Ok((predictor.clone(), false)),
+            _ => Ok((load(model.clone())?, true)),
+        })
+    }
+
+    /// Атомарно заменяет набор моделей.
+    pub fn apply(&self, update: RegistryUpdate<P>) -> Vec<String> {
+        *self.state.write().unwrap() = Arc::new(update.models);
+        update.changed
+    }
+
+    fn snapshot(&self) -> Arc<Models<P>> {
+        self.state.read().unwrap().clone()
+    }
+
+    fn load_update(
+        task: &str,
+        config: &ModelSet,
+        load: impl Fn(&str, &ModelData) -> Result<(P, bool)>,
+    ) -> Result<RegistryUpdate<P>> {
+        let default = config
+            .default_name()
+            .map_err(|err| Error::Config(format!("`{task}`: {err}")))?;
+
+        let mut models = BTreeMap::new();
+        let mut changed = vec![];
+        for (name, model) in config.models() {
+            let (predictor, loaded) = load(name, model)?;
+            if loaded {
+                changed.push(name.to_string());
+            }
+            models.insert(name.to_string(), (model.clone(), predictor));
+        }
+
+        Ok(RegistryUpdate {
+            models: Models {
+                default: default.to_string(),
+                models,
+            },
+            changed,
+        })
+    }
+}
@@ -1,5 +1,5 @@
 mod textual;
 mod visual;
 
-pub use textual::predictor::ImageTextualize;
+pub use textual::predictor::{pad_token_id, ImageTextualize};
 pub use visual::predictor::ImageVisualize;
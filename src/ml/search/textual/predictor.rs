@@ -1,107 +1,301 @@
-use itertools::Itertools;
-use ndarray::Array;
-use ort::{inputs, GraphOptimizationLevel, Session, SessionOutputs};
+use std::sync::Arc;
+
+use ndarray::{s, Array, Array2};
+use ort::inputs;
 use tokenizers::{Encoding, Tokenizer};
 
+use crate::{
+    config::{BatchingOptions, ModelData},
+    error::{Error, Result},
+    ml::{
+        batching::{split_rows, Batcher},
+        fingerprint::ModelFingerprint,
+        inference_pool::InferencePool,
+        predictor::{ModelMetadata, Predictor},
+        session_pool::{PoolStatus, SessionPool},
+        tasks::TextEmbedding,
+        validation::{batch_limit, check_batch_dimension, check_count, check_tensor},
+    },
+};
+
 #[derive(Debug, Clone)]
 pub struct ImageTextualize {
-    pub model_path: String,
-    pub model_name: String,
+    metadata: ModelMetadata,
+    max_batch_size: usize,
+    tokenizer: Arc<Tokenizer>,
+    /// Идентификатор токена дополнения из настроек токенизатора.
+    pad_id: i64,
+    session: Arc<SessionPool>,
+    batcher: Option<Arc<Batcher<TokenizedText, Vec<f32>>>>,
 }
 
+/// Идентификаторы токенов и маска внимания размера `[1, L]`.
+type TokenizedText = (Array2<i64>, Array2<i64>);
+
+/// Номер выхода модели с эмбеддингом всего текста.
+const EMBEDDING_OUTPUT_INDEX: usize = 1;
+
 impl ImageTextualize {
-    pub fn new(path: String, text_model_for_tokenizer: String) -> Self {
-        ImageTextualize {
-            model_path: path,
-            model_name: text_model_for_tokenizer,
-        }
+    /// Создает модель с токенизатором, загружаемым из Hugging Face Hub.
+    pub fn new(path: String, text_model_for_tokenizer: String) -> Result<Self> {
+        Self::from_config(ModelData {
+            tokenizer_from_hub: true,
+            ..ModelData::new(path, text_model_for_tokenizer)
+        })
+    }
+
+    pub fn from_config(config: ModelData) -> Result<Self> {
+        let fingerprint = ModelFingerprint::verify(&config)?;
+        let tokenizer = Arc::new(Self::create_tokenizer(&config)?);
+        let pad_id = pad_token_id(&tokenizer)?;
+
+        let session = Arc::new(SessionPool::from_config(&config)?);
+        let max_batch_size = batch_limit(session.input_shape(), config.max_batch_size);
+
+        let batcher = config.batching.as_ref().map(|options| {
+            let options = BatchingOptions {
+                max_batch_size: batch_limit(session.input_shape(), options.max_batch_size),
+                ..options.clone()
+            };
+            let session = session.clone();
+            Arc::new(Batcher::new(&config.model_name, &options, move |texts| {
+                Self::run_batch(&session, pad_id, texts)
+            }))
+        });
+
+        Ok(ImageTextualize {
+            max_batch_size,
+            tokenizer,
+            pad_id,
+            metadata: ModelMetadata::new(config, &session, fingerprint),
+            session,
+            batcher,
+        })
+    }
+
+    pub fn pool_status(&self) -> PoolStatus {
+        self.session.status()
     }
 
-    pub fn predict(&self, text: &str) -> Vec<f32> {
-        let session = self.load_session();
-        let tokenizer = Self::create_tokenizer(&self.model_name);
-        let preprocessed = tokenizer.encode(text, true).unwrap();
+    /// Запускает модель на батче текстов, дополняя их до длины самого длинного.
+    ///
+    /// Если токенизатор уже дополнил тексты до одной длины, дополнительное
+    /// выравнивание не требуется.
+    fn run_batch(
+        session: &SessionPool,
+        pad_id: i64,
+        texts: Vec<TokenizedText>,
+    ) -> Result<Vec<Vec<f32>>> {
+        let max_len = texts.iter().map(|(ids, _)| ids.ncols()).max().unwrap_or(0);
 
-        let binding = vec![text.to_string()];
-        let input_ids_vector = Self::get_input_ids_vector(preprocessed.clone(), &binding);
+        // Дополнение справа токеном дополнения и нулевой маской
+        let mut input_ids = Array2::<i64>::from_elem((texts.len(), max_len), pad_id);
+        let mut attention_mask = Array2::<i64>::zeros((texts.len(), max_len));
 
-        let binding = vec![text.to_string()];
-        let attention_mask_vector = Self::get_attention_mask_vector(preprocessed, &binding);
+        for (row, (ids, mask)) in texts.iter().enumerate() {
+            let len = ids.ncols();
+            input_ids.slice_mut(s![row, ..len]).assign(&ids.row(0));
+            attention_mask
+                .slice_mut(s![row, ..len])
+                .assign(&mask.row(0));
+        }
+
+        Self::run_tensors(session, (input_ids, attention_mask))
+    }
 
-        let outputs = session
-            .run(inputs![input_ids_vector, attention_mask_vector].unwrap())
-            .unwrap();
+    /// Запускает модель на уже дополненных тензорах `[B, L]`.
+    fn run_tensors(
+        session: &SessionPool,
+        (input_ids, attention_mask): TokenizedText,
+    ) -> Result<Vec<Vec<f32>>> {
+        let batch_size = input_ids.nrows();
+        let outputs = session.run(inputs![input_ids, attention_mask]?)?;
 
-        try_extract(outputs, 1)
+        let embeddings = outputs
+            .get(EMBEDDING_OUTPUT_INDEX)
+            .ok_or_else(|| {
+                Error::ModelOutput(format!(
+                    "text model must have at least {} outputs, got {}",
+                    EMBEDDING_OUTPUT_INDEX + 1,
+                    outputs.len()
+                ))
+            })?
+            .try_extract_tensor::<f32>()?;
+
+        split_rows(embeddings, batch_size)
     }
 
-    fn create_tokenizer(text_model_for_tokenizer: &str) -> Tokenizer {
-        let mut tokenizer = Tokenizer::from_pretrained(text_model_for_tokenizer, None).unwrap();
-        tokenizer.with_padding(Some(tokenizers::PaddingParams {
-            strategy: tokenizers::PaddingStrategy::BatchLongest,
-            direction: tokenizers::PaddingDirection::Right,
-            pad_to_multiple_of: None,
-            pad_id: 0,
-            pad_type_id: 0,
-            pad_token: "[PAD]".to_string(),
-        }));
-        tokenizer
+    /// Загружает токенизатор из локального файла `tokenizer_path`.
+    ///
+    /// Загрузка из Hugging Face Hub по `model_name` выполняется, только если
+    /// она явно разрешена параметром `tokenizer_from_hub`; ошибка загрузки файла
+    /// при этом записывается в журнал. Настройки дополнения и обрезки берутся
+    /// из самого токенизатора.
+    fn create_tokenizer(config: &ModelData) -> Result<Tokenizer> {
+        if let Some(path) = &config.tokenizer_path {
+            match Tokenizer::from_file(path) {
+                Ok(tokenizer) => return Ok(tokenizer),
+                Err(err) if !config.tokenizer_from_hub => return Err(Error::Tokenizer(err)),
+                Err(err) => log::warn!(
+                    "failed to load tokenizer `{path}`, loading `{}` from the hub: {err}",
+                    config.model_name
+                ),
+            }
+        }
+
+        if !config.tokenizer_from_hub {
+            return Err(Error::Tokenizer(
+                "tokenizer_path is not set and tokenizer_from_hub is disabled".into(),
+            ));
+        }
+
+        Tokenizer::from_pretrained(&config.model_name, None).map_err(Error::Tokenizer)
     }
 
-    fn get_attention_mask_vector(
-        preprocessed: Encoding,
-        text: &Vec<String>,
-    ) -> ndarray::Array<i64, ndarray::Dim<[usize; 2]>> {
-        let attention_mask_vector: Vec<i64> = preprocessed
+    /// Преобразует результат токенизации одного текста в тензоры `[1, L]`.
+    fn get_tokenized_text(encoding: &Encoding) -> Result<TokenizedText> {
+        let shape = (1, encoding.len());
+
+        let input_ids = encoding.get_ids().iter().map(|id| *id as i64).collect();
+        let attention_mask = encoding
             .get_attention_mask()
             .iter()
             .map(|b| *b as i64)
-            .collect::<Vec<i64>>();
+            .collect();
+
+        Ok((
+            Array::from_shape_vec(shape, input_ids).map_err(|err| Error::Tokenizer(err.into()))?,
+            Array::from_shape_vec(shape, attention_mask)
+                .map_err(|err| Error::Tokenizer(err.into()))?,
+        ))
+    }
+}
 
-        let mask_shape = (text.len(), attention_mask_vector.len() / text.len());
+/// Токены дополнения словарей BPE и WordPiece в порядке предпочтения.
+const PAD_TOKENS: [&str; 3] = ["<pad>", "[PAD]", "<|endoftext|>"];
 
-        Array::from_shape_vec(mask_shape, attention_mask_vector).unwrap()
+/// Идентификатор токена дополнения из настроек дополнения токенизатора, а если
+/// их нет — идентификатор первого из [`PAD_TOKENS`], найденного в словаре.
+pub fn pad_token_id(tokenizer: &Tokenizer) -> Result<i64> {
+    if let Some(padding) = tokenizer.get_padding() {
+        return Ok(padding.pad_id as i64);
     }
 
-    fn get_input_ids_vector(
-        preprocessed: Encoding,
-        text: &Vec<String>,
-    ) -> ndarray::Array<i64, ndarray::Dim<[usize; 2]>> {
-        let input_ids_vector: Vec<i64> = preprocessed
-            .get_ids()
-            .iter()
-            .map(|b| *b as i64)
-            .collect::<Vec<i64>>();
+    PAD_TOKENS
+        .iter()
+        .find_map(|token| tokenizer.token_to_id(token))
+        .map(|id| id as i64)
+        .ok_or_else(|| {
+            Error::Config(format!(
+                "tokenizer has no padding settings and none of the tokens {PAD_TOKENS:?}"
+            ))
+        })
+}
+
+impl TextEmbedding for ImageTextualize {
+    /// Текст токенизируется в пуле `inference`. С батчингом токены передаются
+    /// планировщику батчей, и результат ожидается асинхронно, не занимая потоки пула;
+    /// иначе модель запускается в пуле (см. [`Predictor::run`]).
+    async fn embed(&self, inference: &InferencePool, text: String) -> Result<Vec<f32>> {
+        let (text, tokens) = {
+            let model = self.clone();
+            inference
+                .spawn(move || {
+                    let tokens = model.preprocess(&text)?;
+                    Ok((text, tokens))
+                })
+                .await?
+        };
 
-        let ids_shape = (text.len(), input_ids_vector.len() / text.len());
+        let embedding = match &self.batcher {
+            Some(batcher) => batcher.submit(tokens).await?,
+            None => {
+                let model = self.clone();
+                inference.spawn(move || model.run(tokens)).await?
+            }
+        };
 
-        Array::from_shape_vec(ids_shape, input_ids_vector).unwrap()
+        self.postprocess(&text, embedding)
     }
 
-    fn load_session(&self) -> Session {
-        Session::builder()
-            .unwrap()
-            .with_optimization_level(GraphOptimizationLevel::Disable)
-            .unwrap()
-            .commit_from_file(&self.model_path)
-            .unwrap()
+    /// Тексты токенизируются вместе и обрабатываются батчами `[B, L]`
+    /// размером не более `max_batch_size`.
+    fn predict_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.max_batch_size) {
+            let encodings = self
+                .tokenizer
+                .encode_batch(chunk.to_vec(), true)
+                .map_err(Error::Tokenizer)?;
+            let batch = encodings
+                .iter()
+                .map(Self::get_tokenized_text)
+                .collect::<Result<Vec<_>>>()?;
+
+            for (text, embedding) in
+                chunk
+                    .iter()
+                    .zip(Self::run_batch(&self.session, self.pad_id, batch)?)
+            {
+                embeddings.push(self.postprocess(text, embedding)?);
+            }
+        }
+
+        Ok(embeddings)
     }
 }
 
-fn try_extract(outputs: SessionOutputs, embed_index: usize) -> Vec<f32> {
-    let binding = outputs[embed_index].try_extract_tensor().unwrap();
-    let embeddings = binding.view();
-    let seq_len = embeddings
-        .shape()
-        .first()
-        .ok_or("cannot find seq_len with index 0 in text embeddings")
-        .unwrap();
-    let embeddings: Vec<f32> = embeddings
-        .iter()
-        .copied()
-        .chunks(*seq_len)
+impl Predictor for ImageTextualize {
+    type Input<'a> = &'a str;
+    type Output = Vec<f32>;
+    type Tensor = TokenizedText;
+    type RawOutput = Vec<f32>;
+
+    fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    fn preprocess(&self, text: &str) -> Result<TokenizedText> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(Error::Tokenizer)?;
+
+        Self::get_tokenized_text(&encoding)
+    }
+
+    /// Запускает модель сразу, без планировщика батчей (см. [`TextEmbedding::embed`]).
+    fn run(&self, text: TokenizedText) -> Result<Vec<f32>> {
+        Self::run_batch(&self.session, self.pad_id, vec![text])?
+            .pop()
+            .ok_or_else(|| Error::ModelOutput("text embeddings are empty".into()))
+    }
+
+    fn postprocess(&self, _: &str, embedding: Vec<f32>) -> Result<Vec<f32>> {
+        Ok(embedding)
+    }
+
+    /// Ожидаются входы `input_ids` и `attention_mask` размера `[B, L]` с динамическим
+    /// размером батча или `B = 1` и эмбеддинг текста `[B, D]` на выходе
+    /// `EMBEDDING_OUTPUT_INDEX`. При `B = 1` тексты подаются в модель по одному.
+    fn validate(&self) -> Vec<String> {
+        let (inputs, outputs) = (&self.metadata.inputs, &self.metadata.outputs);
+
+        [
+            check_count("input", inputs, 2),
+            check_tensor("input", inputs, 0, &[-1, -1]),
+            check_tensor("input", inputs, 1, &[-1, -1]),
+            check_batch_dimension(&self.metadata.input_shape),
+            check_tensor("output", outputs, EMBEDDING_OUTPUT_INDEX, &[-1, -1]),
+        ]
         .into_iter()
         .flatten()
-        .collect();
-    embeddings
+        .collect()
+    }
+
+    fn warmup(&self) -> Result<()> {
+        let text = self.preprocess("warmup")?;
+        Self::run_batch(&self.session, self.pad_id, vec![text])?;
+        Ok(())
+    }
 }
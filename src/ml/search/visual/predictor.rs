@@ -1,52 +1,83 @@
+use std::sync::Arc;
+
 use image::{imageops::FilterType, DynamicImage, RgbImage};
-use itertools::Itertools;
-use ort::{inputs, GraphOptimizationLevel, Session};
+use ndarray::Array4;
+use ort::inputs;
+
+use crate::{
+    config::{BatchingOptions, ModelData},
+    error::{Error, Result},
+    ml::{
+        batching::{split_rows, stack_batch, Batcher},
+        fingerprint::ModelFingerprint,
+        inference_pool::InferencePool,
+        predictor::{ModelMetadata, Predictor},
+        session_pool::{PoolStatus, SessionPool},
+        tasks::ImageEmbedding,
+        validation::{batch_limit, check_batch_dimension, check_count, check_tensor},
+    },
+};
 
 #[derive(Debug, Clone)]
 pub struct ImageVisualize {
-    pub model_path: String,
-    pub model_name: String,
+    metadata: ModelMetadata,
+    max_batch_size: usize,
+    session: Arc<SessionPool>,
+    batcher: Option<Arc<Batcher<Array4<f32>, Vec<f32>>>>,
 }
 
 impl ImageVisualize {
-    pub fn new(path: String, name: String) -> Self {
-        ImageVisualize {
-            model_path: path,
-            model_name: name,
-        }
+    pub fn new(path: String, name: String) -> Result<Self> {
+        Self::from_config(ModelData::new(path, name))
     }
 
-    pub fn predict(&self, dyn_image: DynamicImage) -> Vec<f32> {
-        let session = self.load_session();
+    pub fn from_config(config: ModelData) -> Result<Self> {
+        let fingerprint = ModelFingerprint::verify(&config)?;
+        let session = Arc::new(SessionPool::from_config(&config)?);
+        let max_batch_size = batch_limit(session.input_shape(), config.max_batch_size);
 
-        let tensor = Self::get_tensor(
-            &dyn_image
-                .resize_to_fill(224, 224, FilterType::CatmullRom)
-                .to_rgb8(),
-        );
-
-        let outputs = session.run(inputs![tensor].unwrap()).unwrap();
-        let tensor = outputs[0].try_extract_tensor().unwrap();
-        let embeddings = tensor.view();
-
-        let seq_len = embeddings.shape().get(1).unwrap();
-
-        embeddings
-            .iter()
-            .copied()
-            .chunks(*seq_len)
-            .into_iter()
-            .map(|b| b.collect())
-            .collect::<Vec<Vec<f32>>>()[0]
-            .clone()
+        let batcher = config.batching.as_ref().map(|options| {
+            let options = BatchingOptions {
+                max_batch_size: batch_limit(session.input_shape(), options.max_batch_size),
+                ..options.clone()
+            };
+            let session = session.clone();
+            Arc::new(Batcher::new(&config.model_name, &options, move |tensors| {
+                Self::run_batch(&session, &tensors)
+            }))
+        });
+
+        Ok(ImageVisualize {
+            max_batch_size,
+            metadata: ModelMetadata::new(config, &session, fingerprint),
+            session,
+            batcher,
+        })
+    }
+
+    pub fn pool_status(&self) -> PoolStatus {
+        self.session.status()
+    }
+
+    /// Запускает модель на батче изображений `[B, 3, 224, 224]`.
+    fn run_batch(session: &SessionPool, tensors: &[Array4<f32>]) -> Result<Vec<Vec<f32>>> {
+        let batch = stack_batch(tensors)?;
+        let outputs = session.run(inputs![batch]?)?;
+
+        let embeddings = outputs
+            .first()
+            .ok_or_else(|| Error::ModelOutput("image model has no outputs".into()))?
+            .try_extract_tensor::<f32>()?;
+
+        split_rows(embeddings, tensors.len())
     }
 
-    fn get_tensor(image: &RgbImage) -> ndarray::Array<f32, ndarray::Dim<[usize; 4]>> {
+    fn get_tensor(image: &RgbImage) -> Array4<f32> {
         let mut pixels =
             ndarray::Array::<f32, ndarray::Dim<[usize; 4]>>::zeros(ndarray::Dim([1, 3, 224, 224]));
 
-        let mean = vec![0.48145466, 0.4578275, 0.40821073];
-        let std = vec![0.26862954, 0.261_302_6, 0.27577711];
+        let mean = [0.48145466, 0.4578275, 0.40821073];
+        let std = [0.26862954, 0.261_302_6, 0.275_777_1];
         for (x, y, pixel) in image.enumerate_pixels() {
             let (x, y) = (x as usize, y as usize);
 
@@ -57,13 +88,99 @@ impl ImageVisualize {
 
         pixels
     }
+}
+
+impl ImageEmbedding for ImageVisualize {
+    /// Изображение подготавливается в пуле `inference`. С батчингом тензор передается
+    /// планировщику батчей, и результат ожидается асинхронно, не занимая потоки пула;
+    /// иначе модель запускается в пуле (см. [`Predictor::run`]).
+    async fn embed(&self, inference: &InferencePool, image: DynamicImage) -> Result<Vec<f32>> {
+        let (image, tensor) = {
+            let model = self.clone();
+            inference
+                .spawn(move || {
+                    let tensor = model.preprocess(&image)?;
+                    Ok((image, tensor))
+                })
+                .await?
+        };
+
+        let embedding = match &self.batcher {
+            Some(batcher) => batcher.submit(tensor).await?,
+            None => {
+                let model = self.clone();
+                inference.spawn(move || model.run(tensor)).await?
+            }
+        };
+
+        self.postprocess(&image, embedding)
+    }
+
+    /// Изображения обрабатываются батчами размером не более `max_batch_size`.
+    fn predict_batch(&self, images: &[DynamicImage]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(images.len());
+        for chunk in images.chunks(self.max_batch_size) {
+            let tensors = chunk
+                .iter()
+                .map(|image| self.preprocess(image))
+                .collect::<Result<Vec<_>>>()?;
+
+            for (image, embedding) in chunk.iter().zip(Self::run_batch(&self.session, &tensors)?) {
+                embeddings.push(self.postprocess(image, embedding)?);
+            }
+        }
+
+        Ok(embeddings)
+    }
+}
+
+impl Predictor for ImageVisualize {
+    type Input<'a> = &'a DynamicImage;
+    type Output = Vec<f32>;
+    type Tensor = Array4<f32>;
+    type RawOutput = Vec<f32>;
+
+    fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    fn preprocess(&self, dyn_image: &DynamicImage) -> Result<Array4<f32>> {
+        Ok(Self::get_tensor(
+            &dyn_image
+                .resize_to_fill(224, 224, FilterType::CatmullRom)
+                .to_rgb8(),
+        ))
+    }
+
+    /// Запускает модель сразу, без планировщика батчей (см. [`ImageEmbedding::embed`]).
+    fn run(&self, tensor: Array4<f32>) -> Result<Vec<f32>> {
+        Self::run_batch(&self.session, &[tensor])?
+            .pop()
+            .ok_or_else(|| Error::ModelOutput("image embeddings are empty".into()))
+    }
+
+    fn postprocess(&self, _: &DynamicImage, embedding: Vec<f32>) -> Result<Vec<f32>> {
+        Ok(embedding)
+    }
+
+    /// Ожидаются вход `[B, 3, 224, 224]` с динамическим размером батча или `B = 1`
+    /// и эмбеддинг `[B, D]`. При `B = 1` изображения подаются в модель по одному.
+    fn validate(&self) -> Vec<String> {
+        let (inputs, outputs) = (&self.metadata.inputs, &self.metadata.outputs);
+
+        [
+            check_count("input", inputs, 1),
+            check_tensor("input", inputs, 0, &[-1, 3, 224, 224]),
+            check_batch_dimension(&self.metadata.input_shape),
+            check_tensor("output", outputs, 0, &[-1, -1]),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
 
-    fn load_session(&self) -> Session {
-        Session::builder()
-            .unwrap()
-            .with_optimization_level(GraphOptimizationLevel::Disable)
-            .unwrap()
-            .commit_from_file(&self.model_path)
-            .unwrap()
+    fn warmup(&self) -> Result<()> {
+        Self::run_batch(&self.session, &[Array4::zeros((1, 3, 224, 224))])?;
+        Ok(())
     }
 }
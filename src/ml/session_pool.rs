@@ -0,0 +1,272 @@
+use std::{
+    ops::Deref,
+    sync::{Condvar, Mutex},
+};
+
+use ort::{
+    CPUExecutionProvider, DynValue, GraphOptimizationLevel, Session, SessionInputs, ValueType,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{ModelData, OptimizationLevel, SessionOptions},
+    error::{Error, Result},
+    ml::predictor::TensorInfo,
+};
+
+/// Ограниченный пул сессий `ort::Session` одной модели.
+///
+/// Запросы получают сессии строго в порядке очереди (FIFO), поэтому при нагрузке
+/// они ожидают освобождения сессии, а не занимают все ядра процессора одновременно.
+#[derive(Debug)]
+pub struct SessionPool {
+    inputs: Vec<TensorInfo>,
+    outputs: Vec<TensorInfo>,
+    sessions: FifoPool<Session>,
+}
+
+/// Текущая загрузка пула сессий.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolStatus {
+    pub pool_size: usize,
+    pub in_use: usize,
+    pub queue_depth: usize,
+}
+
+/// Сессия, взятая из пула. Возвращается обратно при уничтожении.
+pub type SessionGuard<'a> = PoolGuard<'a, Session>;
+
+impl SessionPool {
+    /// Создает пул из `size` сессий, каждая из которых собирается через `load_session`.
+    pub fn new(size: usize, load_session: impl Fn() -> Result<Session>) -> Result<Self> {
+        let free: Vec<Session> = (0..size.max(1))
+            .map(|_| load_session())
+            .collect::<Result<_>>()?;
+
+        let inputs = free[0]
+            .inputs
+            .iter()
+            .map(|input| tensor_info(&input.name, &input.input_type))
+            .collect();
+        let outputs = free[0]
+            .outputs
+            .iter()
+            .map(|output| tensor_info(&output.name, &output.output_type))
+            .collect();
+
+        Ok(SessionPool {
+            inputs,
+            outputs,
+            sessions: FifoPool::new(free),
+        })
+    }
+
+    /// Создает пул размера `pool_size` для модели из конфигурации.
+    pub fn from_config(config: &ModelData) -> Result<Self> {
+        Self::new(config.pool_size, || {
+            load_session(&config.model_path, &config.session)
+        })
+    }
+
+    /// Забирает сессию из пула, ожидая своей очереди, если свободных сессий нет.
+    pub fn acquire(&self) -> SessionGuard<'_> {
+        self.sessions.acquire()
+    }
+
+    /// Запускает модель на свободной сессии и возвращает ее выходы
+    /// в порядке их объявления в графе.
+    pub fn run<'i, 'v: 'i, const N: usize>(
+        &self,
+        inputs: impl Into<SessionInputs<'i, 'v, N>>,
+    ) -> Result<Vec<DynValue>> {
+        let session = self.acquire();
+        let mut outputs = session.run(inputs)?;
+
+        Ok(session
+            .outputs
+            .iter()
+            .filter_map(|output| outputs.remove(output.name.as_str()))
+            .collect())
+    }
+
+    /// Форма первого входа модели.
+    pub fn input_shape(&self) -> &[i64] {
+        self.inputs.first().map_or(&[], |input| &input.shape)
+    }
+
+    pub fn inputs(&self) -> &[TensorInfo] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[TensorInfo] {
+        &self.outputs
+    }
+
+    /// Количество запросов, ожидающих свободную сессию.
+    pub fn queue_depth(&self) -> usize {
+        self.sessions.queue_depth()
+    }
+
+    pub fn status(&self) -> PoolStatus {
+        self.sessions.status()
+    }
+}
+
+/// Ограниченный набор объектов, которые выдаются ожидающим строго в порядке очереди.
+///
+/// Каждый ожидающий получает номер; объект достается владельцу номера `now_serving`,
+/// когда в пуле есть свободный объект.
+#[derive(Debug)]
+pub struct FifoPool<T> {
+    size: usize,
+    state: Mutex<PoolState<T>>,
+    released: Condvar,
+}
+
+#[derive(Debug)]
+struct PoolState<T> {
+    free: Vec<T>,
+    next_ticket: u64,
+    now_serving: u64,
+}
+
+impl<T> FifoPool<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        FifoPool {
+            size: items.len(),
+            state: Mutex::new(PoolState {
+                free: items,
+                next_ticket: 0,
+                now_serving: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Забирает объект из пула, ожидая своей очереди, если свободных объектов нет.
+    pub fn acquire(&self) -> PoolGuard<'_, T> {
+        let mut state = self.state.lock().unwrap();
+
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+
+        while ticket != state.now_serving || state.free.is_empty() {
+            state = self.released.wait(state).unwrap();
+        }
+
+        state.now_serving += 1;
+        let item = state.free.pop().unwrap();
+
+        // Следующий в очереди может забрать оставшийся свободный объект
+        self.released.notify_all();
+
+        PoolGuard {
+            pool: self,
+            item: Some(item),
+        }
+    }
+
+    /// Количество ожидающих свободный объект.
+    pub fn queue_depth(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        (state.next_ticket - state.now_serving) as usize
+    }
+
+    pub fn status(&self) -> PoolStatus {
+        let state = self.state.lock().unwrap();
+
+        PoolStatus {
+            pool_size: self.size,
+            in_use: self.size - state.free.len(),
+            queue_depth: (state.next_ticket - state.now_serving) as usize,
+        }
+    }
+
+    fn release(&self, item: T) {
+        self.state.lock().unwrap().free.push(item);
+        self.released.notify_all();
+    }
+}
+
+/// Объект, взятый из пула. Возвращается обратно при уничтожении.
+pub struct PoolGuard<'a, T> {
+    pool: &'a FifoPool<T>,
+    item: Option<T>,
+}
+
+impl<T> Deref for PoolGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<T> Drop for PoolGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.pool.release(item);
+        }
+    }
+}
+
+fn tensor_info(name: &str, value_type: &ValueType) -> TensorInfo {
+    let shape = match value_type {
+        ValueType::Tensor { dimensions, .. } => dimensions.clone(),
+        _ => vec![],
+    };
+
+    TensorInfo {
+        name: name.to_string(),
+        shape,
+    }
+}
+
+pub fn load_session(model_path: &str, options: &SessionOptions) -> Result<Session> {
+    build_session(model_path, options).map_err(|source| Error::ModelLoad {
+        path: model_path.to_string(),
+        source: source.into(),
+    })
+}
+
+fn build_session(model_path: &str, options: &SessionOptions) -> ort::Result<Session> {
+    let mut builder =
+        Session::builder()?.with_optimization_level(options.optimization_level.into())?;
+
+    if let Some(threads) = options.intra_threads {
+        builder = builder.with_intra_threads(threads)?;
+    }
+    if let Some(threads) = options.inter_threads {
+        builder = builder.with_inter_threads(threads)?;
+    }
+    if let Some(parallel) = options.parallel_execution {
+        builder = builder.with_parallel_execution(parallel)?;
+    }
+    if let Some(memory_pattern) = options.memory_pattern {
+        builder = builder.with_memory_pattern(memory_pattern)?;
+    }
+    if let Some(memory_arena) = options.memory_arena {
+        let provider = match memory_arena {
+            true => CPUExecutionProvider::default().with_arena_allocator(),
+            false => CPUExecutionProvider::default(),
+        };
+        builder = builder.with_execution_providers([provider.build()])?;
+    }
+    if let Some(path) = &options.optimized_model_path {
+        builder = builder.with_optimized_model_path(path)?;
+    }
+
+    builder.commit_from_file(model_path)
+}
+
+impl From<OptimizationLevel> for GraphOptimizationLevel {
+    fn from(level: OptimizationLevel) -> Self {
+        match level {
+            OptimizationLevel::Disable => GraphOptimizationLevel::Disable,
+            OptimizationLevel::Level1 => GraphOptimizationLevel::Level1,
+            OptimizationLevel::Level2 => GraphOptimizationLevel::Level2,
+            OptimizationLevel::Level3 => GraphOptimizationLevel::Level3,
+        }
+    }
+}
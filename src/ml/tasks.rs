@@ -0,0 +1,66 @@
+//! Операции задач, через которые работают обработчики запросов.
+//!
+//! Обработчики обобщены по этим трейтам, поэтому их можно проверить
+//! с моделями-заглушками без ONNX Runtime.
+
+use std::future::Future;
+
+use image::DynamicImage;
+
+use crate::{
+    config::DetectionOptions,
+    error::Result,
+    ml::{inference_pool::InferencePool, predictor::Predictor},
+    models::DetectedFaceOutput,
+};
+
+/// Детекция лиц.
+pub trait FaceDetection: Predictor + Clone + 'static {
+    /// Параметры постобработки из конфигурации модели.
+    fn options(&self) -> &DetectionOptions;
+
+    /// Находит лица с параметрами постобработки `options` вместо параметров модели.
+    fn detect(
+        &self,
+        image: &DynamicImage,
+        options: &DetectionOptions,
+    ) -> Result<Vec<DetectedFaceOutput>>;
+}
+
+/// Эмбеддинги найденных лиц.
+pub trait FaceEmbedding: Predictor + Clone + 'static {
+    /// Вычисляет эмбеддинги лиц `faces` изображения в порядке лиц. Лицо, которое
+    /// невозможно выровнять, получает ошибку и не мешает обработке остальных.
+    fn embed_faces(
+        &self,
+        inference: &InferencePool,
+        image: DynamicImage,
+        faces: Vec<DetectedFaceOutput>,
+    ) -> impl Future<Output = Result<Vec<Result<Vec<f32>>>>> + Send;
+}
+
+/// Эмбеддинги текстов.
+pub trait TextEmbedding: Predictor + Clone + 'static {
+    /// Вычисляет эмбеддинг текста.
+    fn embed(
+        &self,
+        inference: &InferencePool,
+        text: String,
+    ) -> impl Future<Output = Result<Vec<f32>>> + Send;
+
+    /// Вычисляет эмбеддинги списка текстов в порядке списка.
+    fn predict_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
+}
+
+/// Эмбеддинги изображений.
+pub trait ImageEmbedding: Predictor + Clone + 'static {
+    /// Вычисляет эмбеддинг изображения.
+    fn embed(
+        &self,
+        inference: &InferencePool,
+        image: DynamicImage,
+    ) -> impl Future<Output = Result<Vec<f32>>> + Send;
+
+    /// Вычисляет эмбеддинги списка изображений в порядке списка.
+    fn predict_batch(&self, images: &[DynamicImage]) -> Result<Vec<Vec<f32>>>;
+}
@@ -0,0 +1,155 @@
+use std::{fmt, path::Path};
+
+use crate::{
+    config::ModelData,
+    error::{Error, Result},
+    ml::{
+        predictor::{Predictor, TensorInfo},
+        registry::ModelRegistry,
+    },
+};
+
+/// Результат проверки моделей при запуске сервиса.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub models: Vec<ModelReport>,
+}
+
+/// Найденные проблемы одной модели.
+#[derive(Debug, Clone)]
+pub struct ModelReport {
+    pub task: String,
+    pub model_path: String,
+    pub problems: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn add(&mut self, task: &str, model_path: &str, problems: Vec<String>) {
+        self.models.push(ModelReport {
+            task: task.to_string(),
+            model_path: model_path.to_string(),
+            problems,
+        });
+    }
+
+    /// Добавляет в отчет результат проверки модели `predictor`.
+    pub fn check<P: Predictor>(&mut self, task: &str, predictor: &P) {
+        self.add(task, &predictor.metadata().model_path, predictor.validate());
+    }
+
+    /// Добавляет в отчет результаты проверки всех моделей задачи `task`.
+    pub fn check_all<P: Predictor + Clone>(&mut self, task: &str, registry: &ModelRegistry<P>) {
+        for (name, predictor) in registry.models() {
+            self.check(&format!("{task}/{name}"), &predictor);
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.models.iter().all(|model| model.problems.is_empty())
+    }
+
+    /// Возвращает ошибку с текстом отчета, если хотя бы одна модель не прошла проверку.
+    pub fn into_result(self) -> Result<Self> {
+        match self.is_ok() {
+            true => Ok(self),
+            false => Err(Error::ModelValidation(self.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for model in &self.models {
+            let status = match model.problems.is_empty() {
+                true => "ok",
+                false => "error",
+            };
+            writeln!(f, "[{status}] {} ({})", model.task, model.model_path)?;
+
+            for problem in &model.problems {
+                writeln!(f, "    - {problem}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Проверяет, что файлы моделей существуют.
+pub fn check_model_files<'a, T: AsRef<str>>(
+    models: impl IntoIterator<Item = (T, &'a ModelData)>,
+) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    for (task, config) in models {
+        let problems = match Path::new(&config.model_path).is_file() {
+            true => vec![],
+            false => vec!["model file does not exist".to_string()],
+        };
+        report.add(task.as_ref(), &config.model_path, problems);
+    }
+
+    report
+}
+
+/// Проверяет количество входов или выходов модели.
+pub fn check_count(kind: &str, tensors: &[TensorInfo], expected: usize) -> Option<String> {
+    (tensors.len() != expected)
+        .then(|| format!("expected {expected} {kind}s, got {}", tensors.len()))
+}
+
+/// Проверяет форму входа или выхода модели с номером `index`.
+///
+/// Размерность `-1` в ожидаемой или фактической форме совпадает с любым значением.
+pub fn check_tensor(
+    kind: &str,
+    tensors: &[TensorInfo],
+    index: usize,
+    expected: &[i64],
+) -> Option<String> {
+    let Some(tensor) = tensors.get(index) else {
+        return Some(format!(
+            "{kind} #{index} is missing, model has {} {kind}s",
+            tensors.len()
+        ));
+    };
+
+    let matches = tensor.shape.len() == expected.len()
+        && tensor
+            .shape
+            .iter()
+            .zip(expected)
+            .all(|(actual, expected)| *actual == -1 || *expected == -1 || actual == expected);
+
+    (!matches).then(|| {
+        format!(
+            "{kind} #{index} `{}` has shape {:?}, expected {expected:?}",
+            tensor.name, tensor.shape
+        )
+    })
+}
+
+/// Размер батча, который можно подать на вход формы `input_shape`.
+///
+/// Если размерность батча фиксирована, `max_batch_size` ограничивается ею;
+/// для динамической размерности (`-1`) возвращается `max_batch_size`.
+pub fn batch_limit(input_shape: &[i64], max_batch_size: usize) -> usize {
+    let max_batch_size = max_batch_size.max(1);
+
+    match input_shape.first() {
+        Some(&batch) if batch > 0 => max_batch_size.min(batch as usize),
+        _ => max_batch_size,
+    }
+}
+
+/// Проверяет, что размерность батча входа динамическая или равна 1.
+///
+/// Батчи переменного размера не совпадут с фиксированной размерностью больше 1.
+pub fn check_batch_dimension(input_shape: &[i64]) -> Option<String> {
+    match input_shape.first() {
+        Some(&batch) if batch > 1 => Some(format!(
+            "input batch dimension is fixed to {batch}, expected dynamic (-1) or 1"
+        )),
+        _ => None,
+    }
+}
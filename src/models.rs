@@ -1,21 +1,60 @@
+use std::collections::BTreeMap;
+
 use axum::body::Bytes;
 use axum_typed_multipart::{FieldData, TryFromMultipart};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
+use crate::{
+    config::{DetectionOptions, Suppression},
+    error::{self, Error, ErrorResponse},
+    ml::{
+        registry::ModelRegistry, session_pool::PoolStatus, ModelFingerprint, ModelMetadata,
+        Predictor,
+    },
+};
+
 #[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
 pub struct DetectedFaceOutput {
     pub score: f32,
+    /// Рамка `[x1, y1, x2, y2]`, обрезанная границами изображения.
     pub bbox: [f32; 4],
-    pub landmarks: [(f32, f32); 5],
+    /// Рамка до обрезки, может выходить за границы изображения.
+    pub unclipped_bbox: [f32; 4],
+    /// Рамка лица выходит за границы изображения.
+    pub truncated: bool,
+    /// Доля площади рамки лица внутри изображения, от 0 до 1.
+    pub visibility: f32,
+    /// Ключевые точки: глаза, нос, уголки рта. Отсутствуют, если детектор их не предсказывает.
+    /// Не обрезаются границами изображения.
+    pub landmarks: Option<[(f32, f32); 5]>,
+}
+
+impl DetectedFaceOutput {
+    /// Лицо, рамка которого еще не обрезана границами изображения.
+    pub fn new(score: f32, bbox: [f32; 4], landmarks: Option<[(f32, f32); 5]>) -> Self {
+        DetectedFaceOutput {
+            score,
+            bbox,
+            unclipped_bbox: bbox,
+            truncated: false,
+            visibility: 1.,
+            landmarks,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
 pub struct RecognizedFaceOutput {
     pub score: f32,
     pub bbox: [f32; 4],
-    pub landmarks: [(f32, f32); 5],
-    pub embedding: Vec<f32>,
+    pub unclipped_bbox: [f32; 4],
+    pub truncated: bool,
+    pub visibility: f32,
+    pub landmarks: Option<[(f32, f32); 5]>,
+    pub embedding: Option<Vec<f32>>,
+    /// Ошибка, из-за которой не удалось получить эмбеддинг лица.
+    pub error: Option<ErrorResponse>,
 }
 
 impl RecognizedFaceOutput {
@@ -23,10 +62,55 @@ impl RecognizedFaceOutput {
         RecognizedFaceOutput {
             score: face.score,
             bbox: face.bbox,
+            unclipped_bbox: face.unclipped_bbox,
+            truncated: face.truncated,
+            visibility: face.visibility,
             landmarks: face.landmarks,
-            embedding,
+            embedding: Some(embedding),
+            error: None,
         }
     }
+
+    pub fn from_error(face: &DetectedFaceOutput, error: &Error) -> Self {
+        RecognizedFaceOutput {
+            score: face.score,
+            bbox: face.bbox,
+            unclipped_bbox: face.unclipped_bbox,
+            truncated: face.truncated,
+            visibility: face.visibility,
+            landmarks: face.landmarks,
+            embedding: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Лица с эмбеддингами и отпечаток модели распознавания, которой они получены.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RecognitionOutput {
+    pub model: ModelFingerprint,
+    pub faces: Vec<RecognizedFaceOutput>,
+}
+
+/// Эмбеддинг и отпечаток модели, которой он получен.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EmbeddingOutput {
+    pub model: ModelFingerprint,
+    pub embedding: Vec<f32>,
+}
+
+/// Эмбеддинги в порядке запроса и отпечаток модели, которой они получены.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EmbeddingsOutput {
+    pub model: ModelFingerprint,
+    pub embeddings: Vec<Vec<f32>>,
+}
+
+/// Результаты обработки изображений по именам файлов и отпечаток модели.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImageEmbeddingsOutput {
+    pub model: ModelFingerprint,
+    pub images: BTreeMap<String, ImageEmbeddingOutput>,
 }
 
 #[derive(TryFromMultipart, Debug)]
@@ -40,7 +124,145 @@ pub struct ImageFormUtopia {
     pub image: Vec<u8>,
 }
 
+#[derive(ToSchema, Debug)]
+pub struct ImagesFormUtopia {
+    pub images: Vec<Vec<u8>>,
+}
+
+/// Результат обработки одного изображения из батча:
+/// эмбеддинг или ошибка, из-за которой его не удалось получить.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImageEmbeddingOutput {
+    pub embedding: Option<Vec<f32>>,
+    pub error: Option<ErrorResponse>,
+}
+
+impl ImageEmbeddingOutput {
+    pub fn from_embedding(embedding: Vec<f32>) -> Self {
+        ImageEmbeddingOutput {
+            embedding: Some(embedding),
+            error: None,
+        }
+    }
+
+    pub fn from_error(error: &Error) -> Self {
+        ImageEmbeddingOutput {
+            embedding: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Выбор модели по имени. Если имя не указано, используется модель по умолчанию.
+#[derive(ToSchema, Debug, IntoParams, Deserialize)]
+pub struct ModelQuery {
+    pub model: Option<String>,
+}
+
+/// Выбор моделей для распознавания лиц: `model` — модель распознавания,
+/// `detector` — модель детекции.
+#[derive(ToSchema, Debug, IntoParams, Deserialize)]
+pub struct RecognitionModelQuery {
+    pub model: Option<String>,
+    pub detector: Option<String>,
+}
+
+/// Параметры детекции для одного запроса. Не указанные параметры берутся
+/// из настроек модели детекции.
+#[derive(ToSchema, Debug, Default, IntoParams, Deserialize)]
+pub struct DetectionQuery {
+    /// Минимальная оценка найденного лица, от 0 до 1.
+    pub score_threshold: Option<f32>,
+    /// Порог IoU подавления пересекающихся рамок, от 0 до 1.
+    pub nms_iou: Option<f32>,
+    /// Максимальное количество лиц в ответе.
+    pub max_faces: Option<usize>,
+    /// Минимальная меньшая сторона рамки лица в пикселях.
+    pub min_face_size: Option<f32>,
+    /// Детекция с несколькими масштабами и отражением. Точнее, но в несколько раз медленнее.
+    pub tta: Option<bool>,
+    /// Способ подавления пересекающихся рамок.
+    pub suppression: Option<Suppression>,
+    /// Параметр `sigma` гауссова Soft-NMS.
+    pub soft_nms_sigma: Option<f32>,
+}
+
+impl DetectionQuery {
+    /// Дополняет параметры модели `defaults` параметрами запроса.
+    pub fn apply(&self, defaults: &DetectionOptions) -> error::Result<DetectionOptions> {
+        let options = DetectionOptions {
+            score_threshold: self.score_threshold.unwrap_or(defaults.score_threshold),
+            nms_iou: self.nms_iou.unwrap_or(defaults.nms_iou),
+            max_faces: self.max_faces.or(defaults.max_faces),
+            min_face_size: self.min_face_size.unwrap_or(defaults.min_face_size),
+            tta: self.tta.unwrap_or(defaults.tta),
+            suppression: self.suppression.unwrap_or(defaults.suppression),
+            soft_nms_sigma: self.soft_nms_sigma.unwrap_or(defaults.soft_nms_sigma),
+        };
+
+        match options.problems().as_slice() {
+            [] => Ok(options),
+            problems => Err(Error::InvalidInput(problems.join("; "))),
+        }
+    }
+}
+
 #[derive(ToSchema, Debug, IntoParams, Deserialize)]
 pub struct TextQuery {
     pub text: String,
 }
+
+/// Загрузка пулов сессий по именам моделей.
+/// Задачи, модели которых не указаны в конфигурации, отсутствуют в ответе.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct PoolsStatusOutput {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub detector: BTreeMap<String, PoolStatus>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub recognizer: BTreeMap<String, PoolStatus>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub textual: BTreeMap<String, PoolStatus>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub visual: BTreeMap<String, PoolStatus>,
+}
+
+/// Загруженные модели. Задачи, модели которых не указаны в конфигурации, отсутствуют в ответе.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ModelsOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detector: Option<TaskModelsOutput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recognizer: Option<TaskModelsOutput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub textual: Option<TaskModelsOutput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visual: Option<TaskModelsOutput>,
+}
+
+/// Результат перезагрузки моделей.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReloadOutput {
+    /// Модели, загруженные заново, в виде `задача/имя`.
+    pub reloaded: Vec<String>,
+}
+
+/// Модели одной задачи.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaskModelsOutput {
+    /// Имя модели, которая используется, если параметр `model` не указан.
+    pub default: String,
+    pub models: BTreeMap<String, ModelMetadata>,
+}
+
+impl TaskModelsOutput {
+    pub fn new<P: Predictor + Clone>(registry: &ModelRegistry<P>) -> Self {
+        TaskModelsOutput {
+            default: registry.default_name(),
+            models: registry
+                .models()
+                .into_iter()
+                .map(|(name, model)| (name, model.metadata().clone()))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Экстракторы запросов, ошибки которых возвращаются в формате [`ErrorResponse`].
+//!
+//! [`ErrorResponse`]: crate::error::ErrorResponse
+
+#[cfg(feature = "search")]
+use axum::{async_trait, extract::Request};
+use axum::{
+    extract::{FromRequest, FromRequestParts},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::error::Error;
+
+/// Параметры строки запроса, как [`axum::extract::Query`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(Error))]
+pub struct Query<T>(pub T);
+
+/// Тело запроса или ответа в формате JSON, как [`axum::Json`].
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(Error))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Форма `multipart/form-data`, как [`axum_typed_multipart::TypedMultipart`].
+#[derive(FromRequest)]
+#[from_request(via(axum_typed_multipart::TypedMultipart), rejection(Error))]
+pub struct TypedMultipart<T>(pub T);
+
+/// Потоковое чтение формы `multipart/form-data`, как [`axum::extract::Multipart`].
+#[cfg(feature = "search")]
+pub struct Multipart(pub axum::extract::Multipart);
+
+#[cfg(feature = "search")]
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for Multipart {
+    type Rejection = Error;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Error> {
+        Ok(Multipart(
+            axum::extract::Multipart::from_request(req, state).await?,
+        ))
+    }
+}
@@ -0,0 +1,237 @@
+use std::sync::Arc;
+
+use axum::{extract::State, routing::post, Router};
+use image::EncodableLayout;
+use utoipa::OpenApi;
+
+use super::{
+    dyn_image_from_bytes,
+    extract::{Json, Query, TypedMultipart},
+    load_checked,
+};
+use crate::{
+    config::{FacialProcessing, Suppression},
+    error::Result,
+    ml::{
+        facial_processing::{FaceDetector, FaceRecognizer},
+        inference_pool::InferencePool,
+        registry::{ModelRegistry, RegistryUpdate},
+        validation::ValidationReport,
+        FaceDetection, FaceEmbedding, ModelFingerprint, Predictor,
+    },
+    models::{
+        DetectedFaceOutput, DetectionQuery, ImageForm, ImageFormUtopia, ModelQuery, ModelsOutput,
+        PoolsStatusOutput, RecognitionModelQuery, RecognitionOutput, RecognizedFaceOutput,
+        TaskModelsOutput,
+    },
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(detecting_faces, recognition_faces),
+    components(schemas(
+        ImageFormUtopia, DetectedFaceOutput, RecognizedFaceOutput, RecognitionOutput,
+        ModelFingerprint, ModelQuery, RecognitionModelQuery, DetectionQuery, Suppression,
+    )),
+    tags((name = "face-processing", description = "Работа с лицами"))
+)]
+pub struct ApiDoc;
+
+/// Модели работы с лицами.
+#[derive(Clone)]
+pub struct FaceState<D = FaceDetector, R = FaceRecognizer> {
+    pub detectors: Arc<ModelRegistry<D>>,
+    pub recognizers: Arc<ModelRegistry<R>>,
+    pub inference: InferencePool,
+}
+
+impl FaceState {
+    pub fn new(config: FacialProcessing, inference: InferencePool) -> Result<Self> {
+        Ok(FaceState {
+            detectors: Arc::new(ModelRegistry::from_config(
+                "detector",
+                &config.detector,
+                FaceDetector::from_config,
+            )?),
+            recognizers: Arc::new(ModelRegistry::from_config(
+                "recognizer",
+                &config.recognizer,
+                FaceRecognizer::from_config,
+            )?),
+            inference,
+        })
+    }
+
+    pub fn validate(&self, report: &mut ValidationReport) {
+        report.check_all("detector", &self.detectors);
+        report.check_all("recognizer", &self.recognizers);
+    }
+
+    pub fn pools_status(&self, output: &mut PoolsStatusOutput) {
+        output.detector = self
+            .detectors
+            .models()
+            .into_iter()
+            .map(|(name, model)| (name, model.pool_status()))
+            .collect();
+        output.recognizer = self
+            .recognizers
+            .models()
+            .into_iter()
+            .map(|(name, model)| (name, model.pool_status()))
+            .collect();
+    }
+
+    pub fn models(&self, output: &mut ModelsOutput) {
+        output.detector = Some(TaskModelsOutput::new(&self.detectors));
+        output.recognizer = Some(TaskModelsOutput::new(&self.recognizers));
+    }
+
+    /// Подготавливает замену моделей, параметры которых изменились в `config`.
+    pub fn prepare(&self, config: FacialProcessing) -> Result<FaceUpdate> {
+        Ok(FaceUpdate {
+            detectors: self
+                .detectors
+                .prepare("detector", &config.detector, |model| {
+                    load_checked("detector", model, FaceDetector::from_config)
+                })?,
+            recognizers: self
+                .recognizers
+                .prepare("recognizer", &config.recognizer, |model| {
+                    load_checked("recognizer", model, FaceRecognizer::from_config)
+                })?,
+        })
+    }
+
+    /// Заменяет модели и добавляет в `reloaded` подписи загруженных заново.
+    ///
+    /// Замена атомарна для каждого реестра задачи, но реестры заменяются по очереди:
+    /// запрос между заменами может получить новую модель одной задачи и старую другой.
+    /// Запросы, уже получившие модель, завершаются на ней.
+    pub fn apply(&self, update: FaceUpdate, reloaded: &mut Vec<String>) {
+        reloaded.extend(
+            self.detectors
+                .apply(update.detectors)
+                .into_iter()
+                .map(|name| format!("detector/{name}")),
+        );
+        reloaded.extend(
+            self.recognizers
+                .apply(update.recognizers)
+                .into_iter()
+                .map(|name| format!("recognizer/{name}")),
+        );
+    }
+
+    pub fn warmup(&self) -> Result<()> {
+        for (_, detector) in self.detectors.models() {
+            detector.warmup()?;
+        }
+        for (_, recognizer) in self.recognizers.models() {
+            recognizer.warmup()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Новые модели, подготовленные для замены текущих.
+pub struct FaceUpdate {
+    detectors: RegistryUpdate<FaceDetector>,
+    recognizers: RegistryUpdate<FaceRecognizer>,
+}
+
+/// Маршруты работы с лицами для моделей детекции `D` и распознавания `R`.
+pub fn routes<D: FaceDetection, R: FaceEmbedding>(state: FaceState<D, R>) -> Router {
+    Router::new()
+        .route("/detecting-faces", post(detecting_faces::<D, R>))
+        .route("/recognition-faces", post(recognition_faces::<D, R>))
+        .with_state(state)
+}
+
+#[utoipa::path(
+    post,
+    path = "/detecting-faces",
+    tag = "face-processing",
+    params(ModelQuery, DetectionQuery),
+    request_body(content_type="multipart/form-data", content=ImageFormUtopia),
+    responses(
+        (status = 200, description = "Информация обработана успешно", body = Vec<DetectedFaceOutput>),
+        (status = 400, description = "Некорректное изображение, форма запроса или параметры детекции", body = ErrorResponse),
+        (status = 404, description = "Модель не найдена", body = ErrorResponse),
+        (status = 413, description = "Тело запроса больше `body_limit`", body = ErrorResponse),
+        (status = 415, description = "Неверный тип содержимого запроса", body = ErrorResponse),
+        (status = 500, description = "Ошибка модели", body = ErrorResponse),
+    )
+)]
+pub async fn detecting_faces<D: FaceDetection, R: FaceEmbedding>(
+    State(state): State<FaceState<D, R>>,
+    Query(model_query): Query<ModelQuery>,
+    Query(detection_query): Query<DetectionQuery>,
+    TypedMultipart(image_form): TypedMultipart<ImageForm>,
+) -> Result<Json<Vec<DetectedFaceOutput>>> {
+    let detector = state.detectors.get(model_query.model.as_deref())?;
+    let options = detection_query.apply(detector.options())?;
+
+    let faces = state
+        .inference
+        .spawn(move || {
+            let image = dyn_image_from_bytes(image_form.image.contents.as_bytes())?;
+            detector.detect(&image, &options)
+        })
+        .await?;
+
+    Ok(Json(faces))
+}
+
+#[utoipa::path(
+    post,
+    path = "/recognition-faces",
+    tag = "face-processing",
+    params(RecognitionModelQuery, DetectionQuery),
+    request_body(content_type="multipart/form-data", content=ImageFormUtopia),
+    responses(
+        (status = 200, description = "Лица с эмбеддингами и отпечаток модели распознавания", body = RecognitionOutput),
+        (status = 400, description = "Некорректное изображение, форма запроса или параметры детекции", body = ErrorResponse),
+        (status = 404, description = "Модель не найдена", body = ErrorResponse),
+        (status = 413, description = "Тело запроса больше `body_limit`", body = ErrorResponse),
+        (status = 415, description = "Неверный тип содержимого запроса", body = ErrorResponse),
+        (status = 500, description = "Ошибка модели", body = ErrorResponse),
+    )
+)]
+pub async fn recognition_faces<D: FaceDetection, R: FaceEmbedding>(
+    State(state): State<FaceState<D, R>>,
+    Query(model_query): Query<RecognitionModelQuery>,
+    Query(detection_query): Query<DetectionQuery>,
+    TypedMultipart(image_form): TypedMultipart<ImageForm>,
+) -> Result<Json<RecognitionOutput>> {
+    let detector = state.detectors.get(model_query.detector.as_deref())?;
+    let options = detection_query.apply(detector.options())?;
+    let recognizer = state.recognizers.get(model_query.model.as_deref())?;
+    let model = recognizer.metadata().fingerprint.clone();
+
+    let (image, faces) = state
+        .inference
+        .spawn(move || {
+            let image = dyn_image_from_bytes(image_form.image.contents.as_bytes())?;
+            let faces = detector.detect(&image, &options)?;
+            Ok((image, faces))
+        })
+        .await?;
+
+    // Лица, которые невозможно выровнять, возвращаются с ошибкой вместо эмбеддинга
+    let embeddings = recognizer
+        .embed_faces(&state.inference, image, faces.clone())
+        .await?;
+
+    let faces = faces
+        .iter()
+        .zip(embeddings)
+        .map(|(face, embedding)| match embedding {
+            Ok(embedding) => RecognizedFaceOutput::from_mergers(face, embedding),
+            Err(err) => RecognizedFaceOutput::from_error(face, &err),
+        })
+        .collect();
+
+    Ok(Json(RecognitionOutput { model, faces }))
+}
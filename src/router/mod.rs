@@ -0,0 +1,390 @@
+use crate::config::{Config, Model};
+use crate::error::{Error, ErrorResponse, Result};
+use crate::ml::{
+    inference_pool::InferencePool,
+    predictor::ModelMetadata,
+    session_pool::PoolStatus,
+    validation::{check_model_files, ValidationReport},
+};
+use crate::models::{ModelsOutput, PoolsStatusOutput, ReloadOutput, TaskModelsOutput};
+
+use axum::{
+    extract::{DefaultBodyLimit, FromRef, State},
+    http::{header::AUTHORIZATION, HeaderMap},
+    routing::{get, post},
+    Json, Router,
+};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+#[cfg(any(feature = "face", feature = "search"))]
+mod extract;
+#[cfg(feature = "face")]
+pub mod face;
+mod reload;
+#[cfg(feature = "search")]
+pub mod search;
+
+#[cfg(feature = "face")]
+pub use face::FaceState;
+#[cfg(feature = "search")]
+pub use search::SearchState;
+
+#[derive(Clone)]
+pub struct AppState {
+    /// Модели работы с лицами, если они указаны в конфигурации.
+    #[cfg(feature = "face")]
+    pub face: Option<FaceState>,
+    /// Модели поиска, если они указаны в конфигурации.
+    #[cfg(feature = "search")]
+    pub search: Option<SearchState>,
+    pub inference: InferencePool,
+    /// Файл конфигурации, из которого перезагружаются модели.
+    pub config_path: Option<PathBuf>,
+    /// Токен служебных маршрутов.
+    pub admin_token: Option<String>,
+    reload_lock: Arc<Mutex<()>>,
+}
+
+impl AppState {
+    /// Загружает модели, проверяет их входы и выходы и выполняет прогревочный запуск.
+    pub fn new(config: Config) -> Result<Self> {
+        check_features(&config.model)?;
+        check_model_files(config.model.configured()).into_result()?;
+
+        let inference = InferencePool::new(config.service.inference_threads);
+
+        let state = AppState {
+            #[cfg(feature = "face")]
+            face: config
+                .model
+                .facial_processing
+                .map(|models| FaceState::new(models, inference.clone()))
+                .transpose()?,
+            #[cfg(feature = "search")]
+            search: config
+                .model
+                .search
+                .map(|models| {
+                    SearchState::new(
+                        models,
+                        inference.clone(),
+                        config.service.max_images_per_request,
+                    )
+                })
+                .transpose()?,
+            inference,
+            config_path: config.path,
+            admin_token: config.service.admin_token,
+            reload_lock: Arc::default(),
+        };
+
+        state.validate().into_result()?;
+        state.warmup()?;
+
+        Ok(state)
+    }
+
+    pub fn validate(&self) -> ValidationReport {
+        #[allow(unused_mut)]
+        let mut report = ValidationReport::default();
+
+        #[cfg(feature = "face")]
+        if let Some(face) = &self.face {
+            face.validate(&mut report);
+        }
+        #[cfg(feature = "search")]
+        if let Some(search) = &self.search {
+            search.validate(&mut report);
+        }
+
+        report
+    }
+
+    pub fn pools_status(&self) -> PoolsStatusOutput {
+        #[allow(unused_mut)]
+        let mut output = PoolsStatusOutput::default();
+
+        #[cfg(feature = "face")]
+        if let Some(face) = &self.face {
+            face.pools_status(&mut output);
+        }
+        #[cfg(feature = "search")]
+        if let Some(search) = &self.search {
+            search.pools_status(&mut output);
+        }
+
+        output
+    }
+
+    pub fn models(&self) -> ModelsOutput {
+        #[allow(unused_mut)]
+        let mut output = ModelsOutput::default();
+
+        #[cfg(feature = "face")]
+        if let Some(face) = &self.face {
+            face.models(&mut output);
+        }
+        #[cfg(feature = "search")]
+        if let Some(search) = &self.search {
+            search.models(&mut output);
+        }
+
+        output
+    }
+
+    /// Проверяет токен служебных маршрутов в заголовке `Authorization: Bearer <токен>`.
+    pub fn authorize(&self, headers: &HeaderMap) -> Result<()> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match (&self.admin_token, token) {
+            (Some(expected), Some(token)) if constant_time_eq(expected, token) => Ok(()),
+            _ => Err(Error::Unauthorized),
+        }
+    }
+
+    /// Перечитывает файл конфигурации и заменяет модели, параметры которых изменились.
+    /// Возвращает подписи загруженных заново моделей.
+    pub fn reload(&self) -> Result<Vec<String>> {
+        let path = self
+            .config_path
+            .as_ref()
+            .ok_or_else(|| Error::Config("the service was started without a config file".into()))?;
+
+        self.reload_models(Config::from_file(path)?.model)
+    }
+
+    /// Загружает новые и измененные модели и подменяет их.
+    ///
+    /// Модели заменяются, только если все они успешно загружены и проверены.
+    /// Каждый реестр моделей заменяется атомарно, реестры — по очереди.
+    /// Запросы, начатые до замены, завершаются на старых моделях.
+    pub fn reload_models(&self, model: Model) -> Result<Vec<String>> {
+        let _reload = self.reload_lock.lock().unwrap();
+
+        check_features(&model)?;
+        check_model_files(model.configured()).into_result()?;
+
+        #[cfg(feature = "face")]
+        let face = match (&self.face, model.facial_processing) {
+            (Some(state), Some(config)) => Some((state, state.prepare(config)?)),
+            (None, None) => None,
+            _ => return Err(section_changed("facial_processing")),
+        };
+        #[cfg(feature = "search")]
+        let search = match (&self.search, model.search) {
+            (Some(state), Some(config)) => Some((state, state.prepare(config)?)),
+            (None, None) => None,
+            _ => return Err(section_changed("search")),
+        };
+
+        #[allow(unused_mut)]
+        let mut reloaded = vec![];
+
+        #[cfg(feature = "face")]
+        if let Some((state, update)) = face {
+            state.apply(update, &mut reloaded);
+        }
+        #[cfg(feature = "search")]
+        if let Some((state, update)) = search {
+            state.apply(update, &mut reloaded);
+        }
+
+        Ok(reloaded)
+    }
+
+    pub fn warmup(&self) -> Result<()> {
+        #[cfg(feature = "face")]
+        if let Some(face) = &self.face {
+            face.warmup()?;
+        }
+        #[cfg(feature = "search")]
+        if let Some(search) = &self.search {
+            search.warmup()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromRef<AppState> for InferencePool {
+    fn from_ref(app_state: &AppState) -> InferencePool {
+        app_state.inference.clone()
+    }
+}
+
+/// Проверяет, что сервис собран с фичами, необходимыми для указанных моделей.
+fn check_features(model: &Model) -> Result<()> {
+    if cfg!(not(feature = "face")) && model.facial_processing.is_some() {
+        return Err(Error::Config(
+            "`model.facial_processing` requires the `face` feature".into(),
+        ));
+    }
+    if cfg!(not(feature = "search")) && model.search.is_some() {
+        return Err(Error::Config(
+            "`model.search` requires the `search` feature".into(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(any(feature = "face", feature = "search"))]
+fn section_changed(section: &str) -> Error {
+    Error::Config(format!(
+        "adding or removing `model.{section}` requires a restart"
+    ))
+}
+
+/// Загружает модель при перезагрузке конфигурации, проверяет ее входы и выходы
+/// и выполняет прогревочный запуск.
+#[cfg(any(feature = "face", feature = "search"))]
+fn load_checked<P: crate::ml::Predictor>(
+    task: &str,
+    config: crate::config::ModelData,
+    load: impl Fn(crate::config::ModelData) -> Result<P>,
+) -> Result<P> {
+    let name = format!("{task}/{}", config.model_name);
+    let predictor = load(config)?;
+
+    let mut report = ValidationReport::default();
+    report.check(&name, &predictor);
+    report.into_result()?;
+
+    predictor.warmup()?;
+
+    Ok(predictor)
+}
+
+/// Собирает приложение. Маршруты моделей регистрируются, только если
+/// эти модели указаны в конфигурации.
+pub fn create_app(swagger_path: String, body_limit: u32, config: Config) -> Result<Router> {
+    #[derive(OpenApi)]
+    #[openapi(
+        paths(
+            pools_status,
+            models,
+        ),
+        components(
+            schemas(
+                PoolStatus, PoolsStatusOutput, ModelMetadata, ModelsOutput, TaskModelsOutput,
+                ReloadOutput, ErrorResponse,
+            )
+        ),
+        tags(
+            (name = "service", description = "Состояние сервиса"),
+        )
+    )]
+    struct ApiDoc;
+
+    #[derive(OpenApi)]
+    #[openapi(
+        paths(reload::reload),
+        modifiers(&AdminToken),
+        tags((name = "admin", description = "Управление сервисом")),
+    )]
+    struct AdminApiDoc;
+
+    let watch_interval = config.service.config_watch_interval_secs;
+    let state = AppState::new(config)?;
+
+    if let (Some(interval), Some(path)) = (watch_interval, state.config_path.clone()) {
+        reload::spawn_config_watcher(state.clone(), path, Duration::from_secs(interval));
+    }
+
+    let mut api = ApiDoc::openapi();
+    let mut router = Router::new()
+        .route("/pools-status", get(pools_status))
+        .route("/models", get(models));
+    // Перезагрузка доступна только с токеном
+    if state.admin_token.is_some() {
+        api.merge(AdminApiDoc::openapi());
+        router = router.route("/admin/reload", post(reload::reload));
+    }
+    #[allow(unused_mut)]
+    let mut router = router.with_state(state.clone());
+
+    #[cfg(feature = "face")]
+    if let Some(face) = state.face {
+        api.merge(face::ApiDoc::openapi());
+        router = router.merge(face::routes(face));
+    }
+    #[cfg(feature = "search")]
+    if let Some(search) = state.search {
+        api.merge(search::ApiDoc::openapi());
+        router = router.merge(search::routes(search));
+    }
+
+    Ok(router
+        .merge(SwaggerUi::new(swagger_path).url("/api-docs/openapi.json", api))
+        .layer(DefaultBodyLimit::max(body_limit as _)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pools-status",
+    tag = "service",
+    responses(
+        (status = 200, description = "Загрузка пулов сессий моделей", body = PoolsStatusOutput)
+    )
+)]
+pub async fn pools_status(State(state): State<AppState>) -> Json<PoolsStatusOutput> {
+    Json(state.pools_status())
+}
+
+#[utoipa::path(
+    get,
+    path = "/models",
+    tag = "service",
+    responses(
+        (status = 200, description = "Описание загруженных моделей", body = ModelsOutput)
+    )
+)]
+pub async fn models(State(state): State<AppState>) -> Json<ModelsOutput> {
+    Json(state.models())
+}
+
+#[cfg(any(feature = "face", feature = "search"))]
+fn dyn_image_from_bytes(image_bytes: &[u8]) -> Result<image::DynamicImage> {
+    let image = image::ImageReader::new(std::io::Cursor::new(image_bytes))
+        .with_guessed_format()
+        .map_err(image::ImageError::IoError)?
+        .decode()?;
+
+    Ok(image)
+}
+
+/// Схема авторизации служебных маршрутов для документации.
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+    }
+}
+
+/// Сравнение строк за время, не зависящее от позиции первого различия.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
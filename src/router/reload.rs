@@ -0,0 +1,78 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use axum::{extract::State, http::HeaderMap, Json};
+
+use super::AppState;
+use crate::{
+    error::{Error, Result},
+    models::ReloadOutput,
+};
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Модели перезагружены", body = ReloadOutput),
+        (status = 401, description = "Не передан или неверен токен `admin_token`", body = ErrorResponse),
+        (status = 500, description = "Ошибка конфигурации или модели, текущие модели не изменены", body = ErrorResponse),
+    )
+)]
+pub async fn reload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ReloadOutput>> {
+    state.authorize(&headers)?;
+
+    let reloaded = tokio::task::spawn_blocking(move || state.reload())
+        .await
+        .map_err(|_| Error::WorkerPanicked)??;
+
+    Ok(Json(ReloadOutput { reloaded }))
+}
+
+/// Периодически проверяет время изменения файла конфигурации
+/// и перезагружает модели, если файл изменился.
+///
+/// После неудачной перезагрузки файл перечитывается только при следующем изменении.
+pub fn spawn_config_watcher(state: AppState, path: PathBuf, interval: Duration) {
+    tokio::spawn(async move {
+        let mut modified = modified_at(&path);
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            let current = modified_at(&path);
+            if current == modified {
+                continue;
+            }
+
+            // Время изменения запоминается и при ошибке: недопустимая конфигурация
+            // не перечитывается на каждой проверке. Файл, прочитанный во время записи,
+            // перечитывается, когда запись завершится и время изменения снова поменяется
+            modified = current;
+
+            let state = state.clone();
+            match tokio::task::spawn_blocking(move || state.reload()).await {
+                Ok(Ok(reloaded)) => log::info!("config reloaded, reloaded models: {reloaded:?}"),
+                Ok(Err(err)) => {
+                    log::error!("failed to reload config, waiting for the next change: {err}")
+                }
+                Err(err) => {
+                    log::error!("config reload panicked, waiting for the next change: {err}")
+                }
+            }
+        }
+    });
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
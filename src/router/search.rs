@@ -0,0 +1,328 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{body::Bytes, extract::State, routing::post, Router};
+use image::EncodableLayout;
+use utoipa::OpenApi;
+
+use super::{
+    dyn_image_from_bytes,
+    extract::{Json, Multipart, Query, TypedMultipart},
+    load_checked,
+};
+use crate::{
+    config::Search,
+    error::{Error, Result},
+    ml::{
+        inference_pool::InferencePool,
+        registry::{ModelRegistry, RegistryUpdate},
+        search::{ImageTextualize, ImageVisualize},
+        validation::ValidationReport,
+        ImageEmbedding, ModelFingerprint, Predictor, TextEmbedding,
+    },
+    models::{
+        EmbeddingOutput, EmbeddingsOutput, ImageEmbeddingOutput, ImageEmbeddingsOutput, ImageForm,
+        ImageFormUtopia, ImagesFormUtopia, ModelQuery, ModelsOutput, PoolsStatusOutput,
+        TaskModelsOutput, TextQuery,
+    },
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(clip_textual, clip_textual_batch, clip_visual, clip_visual_batch),
+    components(schemas(
+        ImageFormUtopia, ImagesFormUtopia, ImageEmbeddingOutput, ImageEmbeddingsOutput,
+        EmbeddingOutput, EmbeddingsOutput, ModelFingerprint, TextQuery, ModelQuery,
+    )),
+    tags((name = "search", description = "Поисковики"))
+)]
+pub struct ApiDoc;
+
+/// Модели поиска по тексту и изображениям.
+#[derive(Clone)]
+pub struct SearchState<T = ImageTextualize, V = ImageVisualize> {
+    pub textuals: Arc<ModelRegistry<T>>,
+    pub visuals: Arc<ModelRegistry<V>>,
+    pub inference: InferencePool,
+    pub max_images_per_request: usize,
+}
+
+impl SearchState {
+    pub fn new(
+        config: Search,
+        inference: InferencePool,
+        max_images_per_request: usize,
+    ) -> Result<Self> {
+        Ok(SearchState {
+            textuals: Arc::new(ModelRegistry::from_config(
+                "textual",
+                &config.textual,
+                ImageTextualize::from_config,
+            )?),
+            visuals: Arc::new(ModelRegistry::from_config(
+                "visual",
+                &config.visual,
+                ImageVisualize::from_config,
+            )?),
+            inference,
+            max_images_per_request,
+        })
+    }
+
+    pub fn validate(&self, report: &mut ValidationReport) {
+        report.check_all("textual", &self.textuals);
+        report.check_all("visual", &self.visuals);
+    }
+
+    pub fn pools_status(&self, output: &mut PoolsStatusOutput) {
+        output.textual = self
+            .textuals
+            .models()
+            .into_iter()
+            .map(|(name, model)| (name, model.pool_status()))
+            .collect();
+        output.visual = self
+            .visuals
+            .models()
+            .into_iter()
+            .map(|(name, model)| (name, model.pool_status()))
+            .collect();
+    }
+
+    pub fn models(&self, output: &mut ModelsOutput) {
+        output.textual = Some(TaskModelsOutput::new(&self.textuals));
+        output.visual = Some(TaskModelsOutput::new(&self.visuals));
+    }
+
+    /// Подготавливает замену моделей, параметры которых изменились в `config`.
+    pub fn prepare(&self, config: Search) -> Result<SearchUpdate> {
+        Ok(SearchUpdate {
+            textuals: self.textuals.prepare("textual", &config.textual, |model| {
+                load_checked("textual", model, ImageTextualize::from_config)
+            })?,
+            visuals: self.visuals.prepare("visual", &config.visual, |model| {
+                load_checked("visual", model, ImageVisualize::from_config)
+            })?,
+        })
+    }
+
+    /// Заменяет модели и добавляет в `reloaded` подписи загруженных заново.
+    ///
+    /// Замена атомарна для каждого реестра задачи, но реестры заменяются по очереди:
+    /// запрос между заменами может получить новую модель одной задачи и старую другой.
+    /// Запросы, уже получившие модель, завершаются на ней.
+    pub fn apply(&self, update: SearchUpdate, reloaded: &mut Vec<String>) {
+        reloaded.extend(
+            self.textuals
+                .apply(update.textuals)
+                .into_iter()
+                .map(|name| format!("textual/{name}")),
+        );
+        reloaded.extend(
+            self.visuals
+                .apply(update.visuals)
+                .into_iter()
+                .map(|name| format!("visual/{name}")),
+        );
+    }
+
+    pub fn warmup(&self) -> Result<()> {
+        for (_, textual) in self.textuals.models() {
+            textual.warmup()?;
+        }
+        for (_, visual) in self.visuals.models() {
+            visual.warmup()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Новые модели, подготовленные для замены текущих.
+pub struct SearchUpdate {
+    textuals: RegistryUpdate<ImageTextualize>,
+    visuals: RegistryUpdate<ImageVisualize>,
+}
+
+/// Маршруты поиска для моделей эмбеддингов текстов `T` и изображений `V`.
+pub fn routes<T: TextEmbedding, V: ImageEmbedding>(state: SearchState<T, V>) -> Router {
+    Router::new()
+        .route("/clip-textual", post(clip_textual::<T, V>))
+        .route("/clip-textual/batch", post(clip_textual_batch::<T, V>))
+        .route("/clip-visual", post(clip_visual::<T, V>))
+        .route("/clip-visual/batch", post(clip_visual_batch::<T, V>))
+        .with_state(state)
+}
+
+#[utoipa::path(
+    post,
+    path = "/clip-textual",
+    tag = "search",
+    params(TextQuery, ModelQuery),
+    responses(
+        (status = 200, description = "Эмбеддинг текста и отпечаток модели", body = EmbeddingOutput),
+        (status = 400, description = "Не передан текст или некорректные параметры запроса", body = ErrorResponse),
+        (status = 404, description = "Модель не найдена", body = ErrorResponse),
+        (status = 500, description = "Ошибка модели или токенизатора", body = ErrorResponse),
+    )
+)]
+pub async fn clip_textual<T: TextEmbedding, V: ImageEmbedding>(
+    State(state): State<SearchState<T, V>>,
+    Query(text_query): Query<TextQuery>,
+    Query(model_query): Query<ModelQuery>,
+) -> Result<Json<EmbeddingOutput>> {
+    let textualize = state.textuals.get(model_query.model.as_deref())?;
+    let model = textualize.metadata().fingerprint.clone();
+
+    let embedding = textualize.embed(&state.inference, text_query.text).await?;
+
+    Ok(Json(EmbeddingOutput { model, embedding }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/clip-textual/batch",
+    tag = "search",
+    params(ModelQuery),
+    request_body(content = Vec<String>, description = "Список текстов"),
+    responses(
+        (status = 200, description = "Эмбеддинги текстов в порядке запроса и отпечаток модели", body = EmbeddingsOutput),
+        (status = 400, description = "Некорректное тело запроса", body = ErrorResponse),
+        (status = 404, description = "Модель не найдена", body = ErrorResponse),
+        (status = 413, description = "Тело запроса больше `body_limit`", body = ErrorResponse),
+        (status = 415, description = "Неверный тип содержимого запроса", body = ErrorResponse),
+        (status = 422, description = "Тело запроса не является списком строк", body = ErrorResponse),
+        (status = 500, description = "Ошибка модели или токенизатора", body = ErrorResponse),
+    )
+)]
+pub async fn clip_textual_batch<T: TextEmbedding, V: ImageEmbedding>(
+    State(state): State<SearchState<T, V>>,
+    Query(model_query): Query<ModelQuery>,
+    Json(texts): Json<Vec<String>>,
+) -> Result<Json<EmbeddingsOutput>> {
+    let textualize = state.textuals.get(model_query.model.as_deref())?;
+    let model = textualize.metadata().fingerprint.clone();
+
+    let embeddings = state
+        .inference
+        .spawn(move || {
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            textualize.predict_batch(&texts)
+        })
+        .await?;
+
+    Ok(Json(EmbeddingsOutput { model, embeddings }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/clip-visual",
+    tag = "search",
+    params(ModelQuery),
+    request_body(content_type="multipart/form-data", content=ImageFormUtopia),
+    responses(
+        (status = 200, description = "Эмбеддинг изображения и отпечаток модели", body = EmbeddingOutput),
+        (status = 400, description = "Некорректное изображение или форма запроса", body = ErrorResponse),
+        (status = 404, description = "Модель не найдена", body = ErrorResponse),
+        (status = 413, description = "Тело запроса больше `body_limit`", body = ErrorResponse),
+        (status = 415, description = "Неверный тип содержимого запроса", body = ErrorResponse),
+        (status = 500, description = "Ошибка модели", body = ErrorResponse),
+    )
+)]
+pub async fn clip_visual<T: TextEmbedding, V: ImageEmbedding>(
+    State(state): State<SearchState<T, V>>,
+    Query(model_query): Query<ModelQuery>,
+    TypedMultipart(image_form): TypedMultipart<ImageForm>,
+) -> Result<Json<EmbeddingOutput>> {
+    let visualize = state.visuals.get(model_query.model.as_deref())?;
+    let model = visualize.metadata().fingerprint.clone();
+
+    let image = state
+        .inference
+        .spawn(move || dyn_image_from_bytes(image_form.image.contents.as_bytes()))
+        .await?;
+    let embedding = visualize.embed(&state.inference, image).await?;
+
+    Ok(Json(EmbeddingOutput { model, embedding }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/clip-visual/batch",
+    tag = "search",
+    params(ModelQuery),
+    request_body(content_type="multipart/form-data", content=ImagesFormUtopia),
+    responses(
+        (status = 200, description = "Эмбеддинги или ошибки изображений по именам файлов и отпечаток модели", body = ImageEmbeddingsOutput),
+        (status = 400, description = "Некорректная форма запроса, слишком много изображений, изображение без имени файла или повторяющиеся имена файлов", body = ErrorResponse),
+        (status = 404, description = "Модель не найдена", body = ErrorResponse),
+        (status = 413, description = "Тело запроса больше `body_limit`", body = ErrorResponse),
+        (status = 415, description = "Неверный тип содержимого запроса", body = ErrorResponse),
+        (status = 500, description = "Ошибка модели", body = ErrorResponse),
+    )
+)]
+pub async fn clip_visual_batch<T: TextEmbedding, V: ImageEmbedding>(
+    State(state): State<SearchState<T, V>>,
+    Query(model_query): Query<ModelQuery>,
+    Multipart(mut multipart): Multipart,
+) -> Result<Json<ImageEmbeddingsOutput>> {
+    let visualize = state.visuals.get(model_query.model.as_deref())?;
+    let model = visualize.metadata().fingerprint.clone();
+
+    // Части формы читаются по одной, поэтому запрос с лишними изображениями
+    // отклоняется, не дочитывая тело
+    let mut images: Vec<(String, Bytes)> = vec![];
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() != Some("images") {
+            return Err(Error::InvalidInput(format!(
+                "unexpected field {:?}, expected `images`",
+                field.name().unwrap_or_default()
+            )));
+        }
+        if images.len() == state.max_images_per_request {
+            return Err(Error::InvalidInput(format!(
+                "request contains more than {} images",
+                state.max_images_per_request
+            )));
+        }
+
+        let name = field
+            .file_name()
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("image #{} has no file name", images.len()))
+            })?
+            .to_string();
+        if images.iter().any(|(other, _)| *other == name) {
+            return Err(Error::InvalidInput(format!(
+                "duplicate image file name: {name}"
+            )));
+        }
+
+        images.push((name, field.bytes().await?));
+    }
+
+    let images = state
+        .inference
+        .spawn(move || {
+            // Изображения, которые не удалось декодировать, не попадают в батч
+            let mut outputs = BTreeMap::new();
+            let mut decoded = Vec::with_capacity(images.len());
+            for (name, bytes) in images {
+                match dyn_image_from_bytes(bytes.as_bytes()) {
+                    Ok(image) => decoded.push((name, image)),
+                    Err(err) => {
+                        outputs.insert(name, ImageEmbeddingOutput::from_error(&err));
+                    }
+                }
+            }
+
+            let (names, images): (Vec<_>, Vec<_>) = decoded.into_iter().unzip();
+            for (name, embedding) in names.into_iter().zip(visualize.predict_batch(&images)?) {
+                outputs.insert(name, ImageEmbeddingOutput::from_embedding(embedding));
+            }
+
+            Ok(outputs)
+        })
+        .await?;
+
+    Ok(Json(ImageEmbeddingsOutput { model, images }))
+}
@@ -1,12 +1,11 @@
-use ml_rust::ml::facial_processing::FaceDetector;
+use ml_rust::ml::{facial_processing::FaceDetector, Predictor};
 use ml_rust::models::DetectedFaceOutput;
 
 const TEST_DATA_DIR: &str = "./tests/assets/with_faces";
 
 fn get_faces(detector: &FaceDetector, image_name: &str) -> Vec<DetectedFaceOutput> {
     let image_one = image::open(format!("{TEST_DATA_DIR}/{image_name}")).unwrap();
-    let faces = detector.predict(&image_one);
-    faces
+    detector.predict(&image_one).unwrap()
 }
 
 #[test]
@@ -15,7 +14,8 @@ fn check_score_and_len() {
         "/home/stepan/rust/projects/recognition_all/ml_rust/models/antelopev2/detection/model.onnx"
             .to_string(),
         "".to_string(),
-    );
+    )
+    .unwrap();
 
     let faces = get_faces(&detector, "test_face_1.jpg");
     assert_eq!(faces.len(), 1);
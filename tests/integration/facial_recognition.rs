@@ -1,4 +1,7 @@
-use ml_rust::ml::facial_processing::{FaceDetector, FaceRecognizer};
+use ml_rust::ml::{
+    facial_processing::{FaceDetector, FaceRecognizer},
+    Predictor,
+};
 use rayon::prelude::*;
 
 const TEST_DATA_DIR: &str = "./tests/assets/with_faces";
@@ -7,11 +10,11 @@ fn get_face_embedding(
     detector: &FaceDetector,
     recognizer: &FaceRecognizer,
     image_name: &str,
-) -> [f32; 512] {
+) -> Vec<f32> {
     let image = image::open(format!("{TEST_DATA_DIR}/{image_name}")).unwrap();
-    let faces = detector.predict(&image);
-    let embeddings = recognizer.predict(&image, &faces);
-    embeddings[0]
+    let faces = detector.predict(&image).unwrap();
+    let mut embeddings = recognizer.predict((&image, &faces)).unwrap();
+    embeddings.swap_remove(0)
 }
 
 pub fn cosine_similarity(vec1: &[f32], vec2: &[f32]) -> f32 {
@@ -32,11 +35,13 @@ fn check_similarity() {
         "/home/stepan/rust/projects/recognition_all/ml_rust/models/antelopev2/detection/model.onnx"
             .to_string(),
         "".to_string(),
-    );
+    )
+    .unwrap();
     let recognizer = FaceRecognizer::new(
         "/home/stepan/rust/projects/recognition_all/ml_rust/models/antelopev2/recognition/model.onnx".to_string(),
         "".to_string(),
-    );
+    )
+    .unwrap();
     let (face1, face2, face3) = (
         get_face_embedding(&detector, &recognizer, "test_face_6.jpg"),
         get_face_embedding(&detector, &recognizer, "test_face_7.jpg"),
@@ -1,2 +1,4 @@
+#[cfg(feature = "face")]
 pub mod facial_detection;
+#[cfg(feature = "face")]
 pub mod facial_recognition;
@@ -0,0 +1,76 @@
+use ml_rust::ml::facial_processing::{anchor_center_array, anchor_centers, decode_anchors};
+use ndarray::Array2;
+
+#[test]
+fn anchor_count_matches_grid() {
+    assert_eq!(anchor_centers(640, 640, 8, 2).len(), 12800);
+    assert_eq!(anchor_centers(640, 640, 16, 2).len(), 3200);
+    assert_eq!(anchor_centers(640, 640, 32, 2).len(), 800);
+    assert_eq!(anchor_centers(640, 480, 32, 1).len(), 20 * 15);
+}
+
+#[test]
+fn anchors_are_listed_by_rows() {
+    let centers = anchor_centers(64, 32, 16, 2);
+
+    assert_eq!(
+        centers,
+        vec![
+            (0., 0.),
+            (0., 0.),
+            (16., 0.),
+            (16., 0.),
+            (32., 0.),
+            (32., 0.),
+            (48., 0.),
+            (48., 0.),
+            (0., 16.),
+            (0., 16.),
+            (16., 16.),
+            (16., 16.),
+            (32., 16.),
+            (32., 16.),
+            (48., 16.),
+            (48., 16.),
+        ]
+    );
+}
+
+#[test]
+fn decodes_only_anchors_above_threshold() {
+    let centers = anchor_center_array(64, 32, 16, 2);
+    let mut scores = Array2::zeros((16, 1));
+    scores[[3, 0]] = 0.9;
+    scores[[10, 0]] = 0.6;
+    let bboxes = Array2::from_elem((16, 4), 0.5);
+    let keypoints = Array2::from_shape_fn((16, 10), |(_, column)| column as f32);
+
+    let faces = decode_anchors(
+        centers.view(),
+        16,
+        scores.view(),
+        bboxes.view(),
+        Some(keypoints.view()),
+        0.5,
+    );
+
+    assert_eq!(faces.len(), 2);
+    assert_eq!(faces[0].score, 0.9);
+    assert_eq!(faces[0].bbox, [8., -8., 24., 8.]);
+    assert_eq!(faces[1].bbox, [8., 8., 24., 24.]);
+    assert_eq!(
+        faces[1].landmarks.unwrap(),
+        [
+            (16., 32.),
+            (48., 64.),
+            (80., 96.),
+            (112., 128.),
+            (144., 160.)
+        ]
+    );
+
+    let without_keypoints =
+        decode_anchors(centers.view(), 16, scores.view(), bboxes.view(), None, 0.7);
+    assert_eq!(without_keypoints.len(), 1);
+    assert!(without_keypoints[0].landmarks.is_none());
+}
@@ -0,0 +1,139 @@
+use std::sync::{Arc, Mutex};
+
+use axum::http::StatusCode;
+use ml_rust::{
+    config::BatchingOptions,
+    error::Error,
+    ml::{
+        batching::{split_rows, stack_batch, Batcher},
+        inference_pool::InferencePool,
+    },
+};
+use ndarray::{array, Array2};
+
+fn options(max_batch_size: usize, max_wait_ms: u64) -> BatchingOptions {
+    BatchingOptions {
+        max_batch_size,
+        max_wait_ms,
+    }
+}
+
+#[tokio::test]
+async fn concurrent_requests_are_batched() {
+    let batch_sizes = Arc::new(Mutex::new(vec![]));
+
+    let sizes = batch_sizes.clone();
+    let batcher = Arc::new(Batcher::new(
+        "test",
+        &options(4, 500),
+        move |items: Vec<i32>| {
+            sizes.lock().unwrap().push(items.len());
+            Ok(items.iter().map(|item| item * 10).collect())
+        },
+    ));
+
+    let handles: Vec<_> = (0..4)
+        .map(|item| {
+            let batcher = batcher.clone();
+            tokio::spawn(async move { batcher.submit(item).await.unwrap() })
+        })
+        .collect();
+
+    let mut results = vec![];
+    for handle in handles {
+        results.push(handle.await.unwrap());
+    }
+
+    assert_eq!(results, vec![0, 10, 20, 30]);
+    assert_eq!(*batch_sizes.lock().unwrap(), vec![4]);
+}
+
+#[tokio::test]
+async fn batch_is_not_limited_by_inference_threads() {
+    let batch_sizes = Arc::new(Mutex::new(vec![]));
+
+    let sizes = batch_sizes.clone();
+    let batcher = Arc::new(Batcher::new(
+        "test",
+        &options(4, 500),
+        move |items: Vec<i32>| {
+            sizes.lock().unwrap().push(items.len());
+            Ok(items)
+        },
+    ));
+    let inference = InferencePool::new(Some(1));
+
+    // Предобработка в пуле из одного потока, ожидание батча — вне пула
+    let handles: Vec<_> = (0..4)
+        .map(|item| {
+            let (batcher, inference) = (batcher.clone(), inference.clone());
+            tokio::spawn(async move {
+                let item = inference.spawn(move || Ok(item)).await.unwrap();
+                batcher.submit(item).await.unwrap()
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(*batch_sizes.lock().unwrap(), vec![4]);
+}
+
+#[tokio::test]
+async fn submit_many_respects_max_batch_size() {
+    let batch_sizes = Arc::new(Mutex::new(vec![]));
+
+    let sizes = batch_sizes.clone();
+    let batcher = Batcher::new("test", &options(3, 10), move |items: Vec<i32>| {
+        sizes.lock().unwrap().push(items.len());
+        Ok(items.iter().map(|item| item + 1).collect())
+    });
+
+    let results = batcher.submit_many((0..7).collect()).await.unwrap();
+
+    assert_eq!(results, vec![1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(*batch_sizes.lock().unwrap(), vec![3, 3, 1]);
+}
+
+#[tokio::test]
+async fn batch_error_is_returned_to_every_request() {
+    let batcher = Batcher::new("test", &options(8, 10), |_: Vec<i32>| {
+        Err::<Vec<i32>, _>(Error::ModelOutput("broken".to_string()))
+    });
+
+    let err = batcher.submit_many(vec![1, 2]).await.unwrap_err();
+
+    assert!(matches!(&err, Error::Batched(err) if matches!(**err, Error::ModelOutput(_))));
+    assert_eq!(err.code(), "model_output");
+    assert!(err.to_string().contains("broken"));
+}
+
+#[tokio::test]
+async fn client_error_in_batch_keeps_status() {
+    let batcher = Batcher::new("test", &options(8, 10), |_: Vec<i32>| {
+        Err::<Vec<i32>, _>(Error::InvalidInput("bad item".to_string()))
+    });
+
+    let single = batcher.submit(1).await.unwrap_err();
+    assert!(matches!(single, Error::InvalidInput(_)));
+
+    let shared = batcher.submit_many(vec![1, 2]).await.unwrap_err();
+    assert_eq!(shared.status_code(), StatusCode::BAD_REQUEST);
+    assert_eq!(shared.code(), "invalid_input");
+}
+
+#[test]
+fn stack_and_split() {
+    let batch = stack_batch(&[array![[1f32, 2.]], array![[3f32, 4.]]]).unwrap();
+    assert_eq!(
+        batch,
+        Array2::from_shape_vec((2, 2), vec![1., 2., 3., 4.]).unwrap()
+    );
+
+    let rows = split_rows(batch.into_dyn().view(), 2).unwrap();
+    assert_eq!(rows, vec![vec![1., 2.], vec![3., 4.]]);
+
+    let wrong = array![1f32, 2.].into_dyn();
+    assert!(split_rows(wrong.view(), 2).is_err());
+}
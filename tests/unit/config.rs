@@ -0,0 +1,196 @@
+use axum::http::{header::AUTHORIZATION, HeaderMap};
+use ml_rust::{
+    config::{Config, ModelData, ModelSet},
+    error::Error,
+    router::AppState,
+};
+
+const SERVICE: &str = r#"
+[service]
+host = "0.0.0.0"
+port = 3003
+swagger_path = "/swagger-ui"
+body_limit = 100000000
+"#;
+
+#[test]
+fn model_sections_are_optional() {
+    let config: Config = toml::from_str(&format!("{SERVICE}\n[model]\n")).unwrap();
+
+    assert!(config.model.facial_processing.is_none());
+    assert!(config.model.search.is_none());
+    assert!(config.model.configured().is_empty());
+
+    let state = AppState::new(config).unwrap();
+    assert!(state.validate().models.is_empty());
+}
+
+#[test]
+fn configured_models_are_listed_by_task() {
+    let config: Config = toml::from_str(&format!(
+        r#"{SERVICE}
+[model.facial_processing.detector]
+model_path = "detector.onnx"
+model_name = "detector"
+
+[model.facial_processing.recognizer]
+model_path = "recognizer.onnx"
+model_name = "recognizer"
+"#
+    ))
+    .unwrap();
+
+    let tasks: Vec<_> = config
+        .model
+        .configured()
+        .into_iter()
+        .map(|(task, model)| (task, model.model_path.clone()))
+        .collect();
+    assert_eq!(
+        tasks,
+        [
+            ("detector/detector".to_string(), "detector.onnx".to_string()),
+            (
+                "recognizer/recognizer".to_string(),
+                "recognizer.onnx".to_string()
+            ),
+        ]
+    );
+    assert!(config.model.search.is_none());
+}
+
+#[test]
+fn several_named_models_per_task() {
+    let config: Config = toml::from_str(&format!(
+        r#"{SERVICE}
+[model.facial_processing.detector]
+model_path = "detector.onnx"
+model_name = "detector"
+
+[model.facial_processing.recognizer.antelopev2]
+model_path = "antelopev2.onnx"
+model_name = "antelopev2"
+default = true
+
+[model.facial_processing.recognizer.buffalo_l]
+model_path = "buffalo_l.onnx"
+model_name = "buffalo_l"
+"#
+    ))
+    .unwrap();
+
+    let recognizer = &config.model.facial_processing.as_ref().unwrap().recognizer;
+    let names: Vec<_> = recognizer
+        .models()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names, ["antelopev2", "buffalo_l"]);
+    assert_eq!(recognizer.default_name().unwrap(), "antelopev2");
+
+    assert_eq!(config.model.configured().len(), 3);
+}
+
+#[test]
+fn several_models_require_one_default() {
+    let models = |defaults: [bool; 2]| -> ModelSet {
+        let [first, second] = defaults.map(|default| ModelData {
+            default,
+            ..ModelData::new("model.onnx".to_string(), "model".to_string())
+        });
+        ModelSet::Named([("first".to_string(), first), ("second".to_string(), second)].into())
+    };
+
+    assert_eq!(models([false, true]).default_name().unwrap(), "second");
+    assert!(models([false, false]).default_name().is_err());
+    assert!(models([true, true]).default_name().is_err());
+}
+
+#[test]
+fn reload_without_changes_keeps_models() {
+    let config: Config = toml::from_str(&format!("{SERVICE}\n[model]\n")).unwrap();
+    let model = config.model.clone();
+    let state = AppState::new(config).unwrap();
+
+    assert!(state.reload_models(model).unwrap().is_empty());
+    // Конфигурация прочитана не из файла, перечитывать нечего
+    assert!(matches!(state.reload(), Err(Error::Config(_))));
+}
+
+#[test]
+fn reload_requires_admin_token() {
+    let headers = |value: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, value.parse().unwrap());
+        headers
+    };
+
+    let config: Config = toml::from_str(&format!("{SERVICE}\n[model]\n")).unwrap();
+    let state = AppState::new(config).unwrap();
+    assert!(matches!(
+        state.authorize(&headers("Bearer secret")),
+        Err(Error::Unauthorized)
+    ));
+
+    let config: Config =
+        toml::from_str(&format!("{SERVICE}admin_token = \"secret\"\n[model]\n")).unwrap();
+    let state = AppState::new(config).unwrap();
+    assert!(state.authorize(&headers("Bearer secret")).is_ok());
+    assert!(state.authorize(&headers("Bearer secre")).is_err());
+    assert!(state.authorize(&headers("secret")).is_err());
+    assert!(state.authorize(&HeaderMap::new()).is_err());
+}
+
+#[test]
+fn config_is_read_from_file() {
+    let path = std::env::temp_dir().join(format!("ml_rust_config_{}.toml", std::process::id()));
+    std::fs::write(&path, format!("{SERVICE}\n[model]\n")).unwrap();
+
+    let config = Config::from_file(&path).unwrap();
+    assert_eq!(config.path.as_deref(), Some(path.as_path()));
+
+    std::fs::write(&path, "[service]\nhost = 1").unwrap();
+    assert!(matches!(Config::from_file(&path), Err(Error::Config(_))));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn model_errors_name_the_field() {
+    let single = toml::from_str::<Config>(&format!(
+        r#"{SERVICE}
+[model.facial_processing.detector]
+model_path = "detector.onnx"
+model_name = "detector"
+pool_size = "two"
+
+[model.facial_processing.recognizer]
+model_path = "recognizer.onnx"
+model_name = "recognizer"
+"#
+    ))
+    .unwrap_err()
+    .to_string();
+    assert!(single.contains("pool_size"), "{single}");
+    assert!(!single.contains("untagged"), "{single}");
+
+    let named = toml::from_str::<Config>(&format!(
+        r#"{SERVICE}
+[model.facial_processing.detector.small]
+model_path = "small.onnx"
+model_name = "small"
+
+[model.facial_processing.detector.large]
+model_path = "large.onnx"
+model_nmae = "large"
+
+[model.facial_processing.recognizer]
+model_path = "recognizer.onnx"
+model_name = "recognizer"
+"#
+    ))
+    .unwrap_err()
+    .to_string();
+    assert!(named.contains("model_name"), "{named}");
+    assert!(!named.contains("untagged"), "{named}");
+}
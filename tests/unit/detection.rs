@@ -0,0 +1,166 @@
+use axum::{extract::Query, http::StatusCode};
+use ml_rust::{
+    config::{DetectionOptions, GridOptions, InputSize, Suppression},
+    error::{Error, ErrorResponse},
+    models::DetectionQuery,
+};
+#[cfg(feature = "face")]
+use ml_rust::{
+    ml::facial_processing::{clip_to_image, final_faces},
+    models::DetectedFaceOutput,
+};
+
+#[test]
+fn query_overrides_model_options() {
+    let defaults = DetectionOptions {
+        max_faces: Some(10),
+        ..DetectionOptions::default()
+    };
+    let query = DetectionQuery {
+        score_threshold: Some(0.8),
+        min_face_size: Some(20.),
+        ..DetectionQuery::default()
+    };
+
+    let options = query.apply(&defaults).unwrap();
+    assert_eq!(
+        options,
+        DetectionOptions {
+            score_threshold: 0.8,
+            nms_iou: 0.5,
+            max_faces: Some(10),
+            min_face_size: 20.,
+            tta: false,
+            suppression: Suppression::Nms,
+            soft_nms_sigma: 0.5,
+        }
+    );
+}
+
+#[test]
+fn invalid_query_is_rejected() {
+    let query = DetectionQuery {
+        nms_iou: Some(1.5),
+        max_faces: Some(0),
+        ..DetectionQuery::default()
+    };
+
+    match query.apply(&DetectionOptions::default()) {
+        Err(Error::InvalidInput(message)) => {
+            assert!(message.contains("nms_iou"));
+            assert!(message.contains("max_faces"));
+        }
+        other => panic!("expected invalid input, got {other:?}"),
+    }
+}
+
+#[test]
+fn malformed_query_is_invalid_input() {
+    let uri = "/detecting-faces?score_threshold=high".parse().unwrap();
+    let err = Error::from(Query::<DetectionQuery>::try_from_uri(&uri).unwrap_err());
+
+    assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    let response = ErrorResponse::from(&err);
+    assert_eq!(response.code, "invalid_input");
+    assert!(response.message.contains("query string"));
+}
+
+#[test]
+fn detection_options_are_read_from_config() {
+    let options: DetectionOptions = toml::from_str("score_threshold = 0.7\nmax_faces = 5").unwrap();
+
+    assert_eq!(options.score_threshold, 0.7);
+    assert_eq!(options.nms_iou, 0.5);
+    assert_eq!(options.max_faces, Some(5));
+    assert!(options.problems().is_empty());
+}
+
+#[test]
+fn grid_input_size_is_square_or_rect() {
+    let grid: GridOptions = toml::from_str("input_size = 480").unwrap();
+    assert_eq!(grid.input_size.dimensions(), (480, 480));
+    assert_eq!(grid.strides, vec![8, 16, 32]);
+    assert!(grid.problems().is_empty());
+
+    let grid: GridOptions = toml::from_str("input_size = [640, 480]\nnum_anchors = 1").unwrap();
+    assert_eq!(grid.input_size.dimensions(), (640, 480));
+    assert_eq!(grid.num_anchors, 1);
+    assert!(grid.problems().is_empty());
+}
+
+#[test]
+fn grid_size_must_be_divisible_by_strides() {
+    let grid = GridOptions {
+        input_size: InputSize::Rect([640, 500]),
+        ..GridOptions::default()
+    };
+
+    assert_eq!(
+        grid.problems(),
+        vec![
+            "input_size 640x500 is not divisible by stride 8",
+            "input_size 640x500 is not divisible by stride 16",
+            "input_size 640x500 is not divisible by stride 32",
+        ]
+    );
+}
+
+#[test]
+fn suppression_is_selected_by_name() {
+    let options: DetectionOptions = toml::from_str("suppression = \"soft_gaussian\"").unwrap();
+    assert_eq!(options.suppression, Suppression::SoftGaussian);
+
+    let uri = "/detecting-faces?suppression=wbf".parse().unwrap();
+    let Query(query) = Query::<DetectionQuery>::try_from_uri(&uri).unwrap();
+    let options = query.apply(&options).unwrap();
+    assert_eq!(options.suppression, Suppression::Wbf);
+}
+
+#[cfg(feature = "face")]
+#[test]
+fn faces_inside_image_are_not_clipped() {
+    let clipped = clip_to_image(
+        DetectedFaceOutput::new(0.9, [10., 20., 50., 70.], None),
+        (100, 80),
+    );
+
+    assert_eq!(clipped.bbox, [10., 20., 50., 70.]);
+    assert_eq!(clipped.unclipped_bbox, clipped.bbox);
+    assert!(!clipped.truncated);
+    assert_eq!(clipped.visibility, 1.);
+}
+
+#[cfg(feature = "face")]
+#[test]
+fn faces_crossing_image_edge_are_clipped() {
+    let clipped = clip_to_image(
+        DetectedFaceOutput::new(0.9, [-20., 40., 20., 120.], None),
+        (100, 80),
+    );
+
+    assert_eq!(clipped.bbox, [0., 40., 20., 80.]);
+    assert_eq!(clipped.unclipped_bbox, [-20., 40., 20., 120.]);
+    assert!(clipped.truncated);
+    assert!((clipped.visibility - 0.25).abs() < 1e-6);
+}
+
+#[cfg(feature = "face")]
+#[test]
+fn faces_outside_image_are_dropped_before_limit() {
+    let faces = vec![
+        DetectedFaceOutput::new(0.95, [120., 10., 150., 40.], None),
+        DetectedFaceOutput::new(0.9, [10., 10., 40., 40.], None),
+        DetectedFaceOutput::new(0.8, [-40., 50., -10., 70.], None),
+        DetectedFaceOutput::new(0.7, [60., 10., 90., 40.], None),
+    ];
+    let options = DetectionOptions {
+        max_faces: Some(2),
+        ..DetectionOptions::default()
+    };
+
+    let faces = final_faces(faces, &options, (100, 80));
+
+    assert_eq!(faces.len(), 2);
+    assert_eq!(faces[0].bbox, [10., 10., 40., 40.]);
+    assert_eq!(faces[1].bbox, [60., 10., 90., 40.]);
+}
@@ -0,0 +1,112 @@
+use ml_rust::{
+    config::{GridOptions, HeadOptions},
+    error::Error,
+    ml::{facial_processing::DetectorHead, TensorInfo},
+};
+
+fn tensors(shapes: &[&[i64]]) -> Vec<TensorInfo> {
+    shapes
+        .iter()
+        .enumerate()
+        .map(|(index, shape)| TensorInfo {
+            name: format!("output_{index}"),
+            shape: shape.to_vec(),
+        })
+        .collect()
+}
+
+fn head(config: &str, grid: &GridOptions, outputs: &[TensorInfo]) -> DetectorHead {
+    let options: HeadOptions = toml::from_str(config).unwrap();
+    DetectorHead::new(&options, grid, outputs).unwrap()
+}
+
+#[test]
+fn default_scrfd_layout_is_valid() {
+    let outputs = tensors(&[
+        &[12800, 1],
+        &[3200, 1],
+        &[800, 1],
+        &[12800, 4],
+        &[3200, 4],
+        &[800, 4],
+        &[12800, 10],
+        &[3200, 10],
+        &[800, 10],
+    ]);
+
+    let head = DetectorHead::new(&HeadOptions::default(), &GridOptions::default(), &outputs);
+    assert!(head.unwrap().validate(&outputs).is_empty());
+}
+
+#[test]
+fn scrfd_without_keypoints_with_five_strides_and_batch() {
+    let grid = GridOptions {
+        strides: vec![8, 16, 32, 64, 128],
+        num_anchors: 1,
+        ..GridOptions::default()
+    };
+    let outputs = tensors(&[
+        &[1, 6400, 1],
+        &[1, 1600, 1],
+        &[1, 400, 1],
+        &[1, 100, 1],
+        &[1, 25, 1],
+        &[1, 6400, 4],
+        &[1, 1600, 4],
+        &[1, 400, 4],
+        &[1, 100, 4],
+        &[1, 25, 4],
+    ]);
+
+    let head = head("type = \"scrfd\"\nkeypoints = false", &grid, &outputs);
+    assert!(head.validate(&outputs).is_empty());
+
+    let mut wrong = outputs.clone();
+    wrong[4].shape = vec![1, 24, 1];
+    assert_eq!(
+        head.validate(&wrong),
+        vec!["output #4 `output_4` has shape [1, 24, 1], expected [1, 25, 1]"]
+    );
+}
+
+#[test]
+fn scrfd_outputs_are_found_by_name() {
+    let grid = GridOptions {
+        strides: vec![32],
+        ..GridOptions::default()
+    };
+    let outputs = tensors(&[&[800, 4], &[800, 10], &[800, 1]]);
+    let config = r#"
+        type = "scrfd"
+        outputs = { scores = ["output_2"], bboxes = ["output_0"], keypoints = ["output_1"] }
+    "#;
+
+    assert!(head(config, &grid, &outputs).validate(&outputs).is_empty());
+
+    let options: HeadOptions = toml::from_str(&config.replace("output_2", "score_32")).unwrap();
+    assert!(matches!(
+        DetectorHead::new(&options, &grid, &outputs),
+        Err(Error::ModelOutput(_))
+    ));
+}
+
+#[test]
+fn yolov8_output_orientation() {
+    let grid = GridOptions::default();
+
+    for shape in [&[1, 20, 8400][..], &[1, 8400, 20], &[-1, 20, -1], &[-1, -1]] {
+        let outputs = tensors(&[shape]);
+        let head = head("type = \"yolov8\"", &grid, &outputs);
+        assert!(head.validate(&outputs).is_empty(), "{shape:?}");
+    }
+
+    let outputs = tensors(&[&[5, 8400]]);
+    let head_without_keypoints = head("type = \"yolov8\"\nkeypoints = false", &grid, &outputs);
+    assert!(head_without_keypoints.validate(&outputs).is_empty());
+
+    for shape in [&[1, 7, 8400][..], &[1, 84, -1], &[5, 8400]] {
+        let outputs = tensors(&[shape]);
+        let head = head("type = \"yolov8\"", &grid, &outputs);
+        assert_eq!(head.validate(&outputs).len(), 1, "{shape:?}");
+    }
+}
@@ -0,0 +1,18 @@
+use axum::{extract::rejection::JsonRejection, http::StatusCode, Json};
+use ml_rust::error::{Error, ErrorResponse};
+
+fn json_rejection(body: &[u8]) -> JsonRejection {
+    Json::<Vec<String>>::from_bytes(body).unwrap_err()
+}
+
+#[test]
+fn rejection_keeps_axum_status() {
+    // Синтаксическая ошибка JSON — 400, данные не того типа — 422
+    let syntax = Error::from(json_rejection(b"[\"a\""));
+    assert_eq!(syntax.status_code(), StatusCode::BAD_REQUEST);
+    assert_eq!(ErrorResponse::from(&syntax).code, "invalid_input");
+
+    let data = Error::from(json_rejection(b"[1]"));
+    assert_eq!(data.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(ErrorResponse::from(&data).code, "invalid_input");
+}
@@ -0,0 +1,64 @@
+use ml_rust::{
+    config::ModelData,
+    error::Error,
+    ml::{fingerprint::file_sha256, ModelFingerprint},
+};
+
+/// SHA-256 строки `abc`.
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+fn model_file(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("ml_rust_{name}_{}.onnx", std::process::id()));
+    std::fs::write(&path, "abc").unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn file_hash_is_hex_sha256() {
+    let path = model_file("hash");
+
+    assert_eq!(file_sha256(&path).unwrap(), ABC_SHA256);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn fingerprint_matches_configured_hash() {
+    let path = model_file("match");
+    let config = ModelData {
+        sha256: Some(ABC_SHA256.to_uppercase()),
+        ..ModelData::new(path.clone(), "model".to_string())
+    };
+
+    let fingerprint = ModelFingerprint::verify(&config).unwrap();
+    assert_eq!(fingerprint.model_name, "model");
+    assert_eq!(fingerprint.sha256, ABC_SHA256);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn fingerprint_rejects_changed_file() {
+    let path = model_file("mismatch");
+    let config = ModelData {
+        sha256: Some("0".repeat(64)),
+        ..ModelData::new(path.clone(), "model".to_string())
+    };
+
+    match ModelFingerprint::verify(&config) {
+        Err(Error::ModelIntegrity { actual, .. }) => assert_eq!(actual, ABC_SHA256),
+        other => panic!("expected integrity error, got {other:?}"),
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn fingerprint_without_file_is_error() {
+    let config = ModelData::new("/nonexistent/model.onnx".to_string(), "model".to_string());
+
+    assert!(matches!(
+        ModelFingerprint::verify(&config),
+        Err(Error::ModelLoad { .. })
+    ));
+}
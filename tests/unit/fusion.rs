@@ -0,0 +1,274 @@
+use ml_rust::{
+    config::DetectionOptions,
+    ml::facial_processing::{
+        fused_faces, iou, mirror_face, non_maximum_suppression, soft_nms, weighted_box_fusion,
+        SoftNmsDecay,
+    },
+    models::DetectedFaceOutput,
+};
+
+fn face(score: f32, bbox: [f32; 4]) -> DetectedFaceOutput {
+    DetectedFaceOutput::new(score, bbox, None)
+}
+
+#[test]
+fn mirrored_face_swaps_left_and_right_points() {
+    let face = DetectedFaceOutput::new(
+        0.9,
+        [10., 20., 50., 70.],
+        Some([(20., 30.), (40., 30.), (30., 45.), (22., 60.), (38., 60.)]),
+    );
+
+    let mirrored = mirror_face(face, 100.);
+
+    assert_eq!(mirrored.bbox, [50., 20., 90., 70.]);
+    assert_eq!(
+        mirrored.landmarks,
+        Some([(60., 30.), (80., 30.), (70., 45.), (62., 60.), (78., 60.)])
+    );
+}
+
+#[test]
+fn overlapping_boxes_of_passes_are_fused() {
+    let passes = vec![
+        vec![
+            face(0.9, [10., 10., 50., 50.]),
+            face(0.8, [200., 200., 240., 240.]),
+        ],
+        vec![face(0.6, [14., 14., 54., 54.])],
+    ];
+
+    let fused = weighted_box_fusion(passes, 0.55);
+
+    assert_eq!(fused.len(), 2);
+
+    // Координаты взвешены оценками 0.9 и 0.6, оценка — средняя по двум проходам
+    let [x1, y1, x2, y2] = fused[0].bbox;
+    for (actual, expected) in [(x1, 11.6), (y1, 11.6), (x2, 51.6), (y2, 51.6)] {
+        assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+    }
+    assert!((fused[0].score - 0.75).abs() < 1e-6);
+
+    // Лицо найдено только одним из двух проходов
+    assert!((fused[1].score - 0.4).abs() < 1e-6);
+}
+
+#[test]
+fn fused_face_below_threshold_is_dropped() {
+    let options = DetectionOptions {
+        score_threshold: 0.5,
+        ..DetectionOptions::default()
+    };
+    let mut passes = vec![vec![face(0.9, [10., 10., 50., 50.])]; 4];
+    passes[0].push(face(0.6, [200., 200., 240., 240.]));
+
+    let faces = fused_faces(passes, 0.55, &options, (300, 300));
+
+    // Лицо 0.6, найденное одним проходом из четырех, после слияния получает оценку 0.15
+    assert_eq!(faces.len(), 1);
+    assert!((faces[0].score - 0.9).abs() < 1e-6);
+}
+
+#[test]
+fn fused_landmarks_are_weighted_by_score() {
+    let with_landmarks = |score, landmark| DetectedFaceOutput {
+        landmarks: Some([landmark; 5]),
+        ..face(score, [10., 10., 50., 50.])
+    };
+    let passes = vec![vec![
+        with_landmarks(0.75, (20., 20.)),
+        with_landmarks(0.25, (24., 28.)),
+        face(0.5, [10., 10., 50., 50.]),
+    ]];
+
+    let fused = weighted_box_fusion(passes, 0.5);
+
+    assert_eq!(fused.len(), 1);
+    let (x, y) = fused[0].landmarks.unwrap()[0];
+    assert!(
+        (x - 21.).abs() < 1e-3 && (y - 22.).abs() < 1e-3,
+        "({x}, {y})"
+    );
+}
+
+/// Псевдослучайные рамки с оценками, отсортированные по убыванию оценки.
+fn random_faces(count: usize) -> Vec<DetectedFaceOutput> {
+    let mut state: u64 = 42;
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) as f32 / (1u64 << 31) as f32
+    };
+
+    let mut faces: Vec<DetectedFaceOutput> = (0..count)
+        .map(|_| {
+            let (x, y, size) = (next() * 2000., next() * 2000., 10. + next() * 90.);
+            face(next(), [x, y, x + size, y + size * 1.2])
+        })
+        .collect();
+    faces.sort_by(|a, b| b.score.total_cmp(&a.score));
+    faces
+}
+
+/// Жадное подавление с попарным сравнением всех рамок.
+fn exhaustive_nms(faces: Vec<DetectedFaceOutput>, max_iou: f32) -> Vec<DetectedFaceOutput> {
+    let mut expected: Vec<DetectedFaceOutput> = vec![];
+    for face in faces {
+        if expected
+            .iter()
+            .all(|selected| iou(&face.bbox, &selected.bbox) <= max_iou)
+        {
+            expected.push(face);
+        }
+    }
+    expected
+}
+
+/// Soft-NMS с уменьшением оценок всех оставшихся рамок после каждого выбора.
+fn exhaustive_soft_nms(
+    mut faces: Vec<DetectedFaceOutput>,
+    decay: SoftNmsDecay,
+    min_score: f32,
+) -> Vec<DetectedFaceOutput> {
+    let weight = |overlap: f32| match decay {
+        SoftNmsDecay::Linear(threshold) if overlap > threshold => 1. - overlap,
+        SoftNmsDecay::Linear(_) => 1.,
+        SoftNmsDecay::Gaussian(sigma) => (-overlap * overlap / sigma).exp(),
+    };
+
+    faces.retain(|face| face.score > min_score);
+
+    let mut selected = vec![];
+    while !faces.is_empty() {
+        let best = (0..faces.len())
+            .reduce(|best, index| match faces[index].score > faces[best].score {
+                true => index,
+                false => best,
+            })
+            .unwrap();
+        let best = faces.remove(best);
+
+        for face in &mut faces {
+            face.score *= weight(iou(&best.bbox, &face.bbox));
+        }
+        faces.retain(|face| face.score > min_score);
+
+        selected.push(best);
+    }
+    selected
+}
+
+/// Слияние рамок со сравнением каждой рамки со всеми объединенными рамками
+/// и пересчетом объединенной рамки по всем ее рамкам.
+fn exhaustive_wbf(faces: Vec<DetectedFaceOutput>, iou_threshold: f32) -> Vec<DetectedFaceOutput> {
+    let fuse = |members: &[DetectedFaceOutput]| {
+        let total: f32 = members.iter().map(|face| face.score).sum();
+        let bbox = std::array::from_fn(|coordinate| {
+            members
+                .iter()
+                .map(|face| face.bbox[coordinate] * face.score)
+                .sum::<f32>()
+                / (total + 1.0e-7)
+        });
+        DetectedFaceOutput::new(total / members.len() as f32, bbox, None)
+    };
+
+    let mut clusters: Vec<(DetectedFaceOutput, Vec<DetectedFaceOutput>)> = vec![];
+    for face in faces {
+        let best = clusters
+            .iter()
+            .enumerate()
+            .map(|(index, (fused, _))| (index, iou(&fused.bbox, &face.bbox)))
+            .filter(|(_, overlap)| *overlap > iou_threshold)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        match best {
+            Some((index, _)) => {
+                let (fused, members) = &mut clusters[index];
+                members.push(face);
+                *fused = fuse(members);
+            }
+            None => clusters.push((face.clone(), vec![face])),
+        }
+    }
+
+    let mut fused: Vec<DetectedFaceOutput> = clusters.into_iter().map(|(fused, _)| fused).collect();
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+    fused
+}
+
+fn assert_same_boxes(actual: &[DetectedFaceOutput], expected: &[DetectedFaceOutput]) {
+    assert_eq!(actual.len(), expected.len());
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert_eq!(actual.bbox, expected.bbox);
+        assert_eq!(actual.score, expected.score);
+    }
+}
+
+#[test]
+fn nms_matches_exhaustive_search() {
+    let faces = random_faces(3000);
+
+    let expected = exhaustive_nms(faces.clone(), 0.3);
+    let selected = non_maximum_suppression(faces.clone(), 0.3);
+    assert_same_boxes(&selected, &expected);
+
+    for decay in [SoftNmsDecay::Linear(0.3), SoftNmsDecay::Gaussian(0.5)] {
+        let expected = exhaustive_soft_nms(faces.clone(), decay, 0.3);
+        let selected = soft_nms(faces.clone(), decay, 0.3);
+        assert_same_boxes(&selected, &expected);
+    }
+
+    let expected = exhaustive_wbf(faces.clone(), 0.3);
+    let fused = weighted_box_fusion(vec![faces], 0.3);
+    assert_same_boxes(&fused, &expected);
+}
+
+#[test]
+fn nms_handles_non_finite_and_huge_boxes() {
+    let mut faces = vec![
+        face(1., [-1.0e30, -1.0e30, 1.0e30, 1.0e30]),
+        face(0.99, [f32::NAN, 0., 10., 10.]),
+        face(0.98, [0., 0., f32::INFINITY, 10.]),
+        face(0.97, [0., 0., 3000., 3000.]),
+    ];
+    faces.extend(random_faces(500));
+
+    let expected = exhaustive_nms(
+        faces
+            .iter()
+            .filter(|face| face.bbox.iter().all(|value| value.is_finite()))
+            .cloned()
+            .collect(),
+        0.3,
+    );
+    let selected = non_maximum_suppression(faces, 0.3);
+
+    assert_same_boxes(&selected, &expected);
+    assert!(selected.iter().all(|face| face.bbox[0].is_finite()));
+}
+
+#[test]
+fn soft_nms_keeps_close_faces_with_lower_score() {
+    let faces = vec![
+        face(0.9, [0., 0., 100., 100.]),
+        face(0.8, [40., 0., 140., 100.]),
+        face(0.7, [1000., 0., 1100., 100.]),
+    ];
+    let overlap = iou(&faces[0].bbox, &faces[1].bbox);
+
+    assert_eq!(
+        non_maximum_suppression(faces.clone(), overlap - 0.01).len(),
+        2
+    );
+
+    let selected = soft_nms(faces.clone(), SoftNmsDecay::Linear(overlap - 0.01), 0.3);
+    assert_eq!(selected.len(), 3);
+    assert_eq!(selected[2].bbox, [40., 0., 140., 100.]);
+    assert!((selected[2].score - 0.8 * (1. - overlap)).abs() < 1e-6);
+
+    let selected = soft_nms(faces, SoftNmsDecay::Gaussian(0.5), 0.3);
+    assert_eq!(selected.len(), 3);
+    assert!((selected[2].score - 0.8 * (-overlap * overlap / 0.5).exp()).abs() < 1e-6);
+}
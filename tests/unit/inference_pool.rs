@@ -0,0 +1,21 @@
+use ml_rust::{error::Error, ml::inference_pool::InferencePool};
+
+#[tokio::test]
+async fn spawn_returns_result() {
+    let pool = InferencePool::new(Some(2));
+    assert_eq!(pool.num_threads(), 2);
+
+    let result = pool.spawn(|| Ok(2 + 2)).await.unwrap();
+    assert_eq!(result, 4);
+}
+
+#[tokio::test]
+async fn spawn_catches_panic() {
+    let pool = InferencePool::new(Some(1));
+
+    let result: Result<(), Error> = pool.spawn(|| panic!("job failed")).await;
+    assert!(matches!(result, Err(Error::WorkerPanicked)));
+
+    // Пул продолжает работать после паники в задаче
+    assert_eq!(pool.spawn(|| Ok(1)).await.unwrap(), 1);
+}
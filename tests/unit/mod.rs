@@ -1 +1,24 @@
+#[cfg(feature = "face")]
+pub mod anchors;
+pub mod batching;
+pub mod config;
+pub mod detection;
+#[cfg(feature = "face")]
+pub mod detector_head;
+pub mod error;
+pub mod fingerprint;
+#[cfg(feature = "face")]
+pub mod fusion;
+pub mod inference_pool;
+pub mod predictor;
+pub mod registry;
+#[cfg(feature = "face")]
+pub mod router;
+pub mod session_pool;
+#[cfg(feature = "face")]
+pub mod tiling;
+#[cfg(feature = "search")]
+pub mod tokenizer;
+#[cfg(feature = "face")]
 pub mod transforms;
+pub mod validation;
@@ -0,0 +1,85 @@
+use std::sync::Mutex;
+
+use ml_rust::{
+    error::{Error, Result},
+    ml::{ModelFingerprint, ModelMetadata, Predictor},
+};
+
+/// Модель-заглушка: удваивает входные значения и записывает порядок вызова шагов.
+struct MockPredictor {
+    metadata: ModelMetadata,
+    steps: Mutex<Vec<&'static str>>,
+}
+
+impl MockPredictor {
+    fn new() -> Self {
+        MockPredictor {
+            metadata: ModelMetadata {
+                model_name: "mock".to_string(),
+                model_path: "".to_string(),
+                input_shape: vec![-1],
+                inputs: vec![],
+                outputs: vec![],
+                fingerprint: ModelFingerprint {
+                    model_name: "mock".to_string(),
+                    sha256: "".to_string(),
+                },
+            },
+            steps: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl Predictor for MockPredictor {
+    type Input<'a> = &'a [f32];
+    type Output = Vec<f32>;
+    type Tensor = Vec<f32>;
+    type RawOutput = Vec<f32>;
+
+    fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    fn preprocess(&self, input: &[f32]) -> Result<Vec<f32>> {
+        self.steps.lock().unwrap().push("preprocess");
+        if input.is_empty() {
+            return Err(Error::InvalidInput("empty input".to_string()));
+        }
+        Ok(input.to_vec())
+    }
+
+    fn run(&self, tensor: Vec<f32>) -> Result<Vec<f32>> {
+        self.steps.lock().unwrap().push("run");
+        Ok(tensor.iter().map(|value| value * 2.).collect())
+    }
+
+    fn postprocess(&self, input: &[f32], raw: Vec<f32>) -> Result<Vec<f32>> {
+        self.steps.lock().unwrap().push("postprocess");
+        assert_eq!(input.len(), raw.len());
+        Ok(raw)
+    }
+}
+
+#[test]
+fn predict_runs_all_steps() {
+    let predictor = MockPredictor::new();
+
+    let output = predictor.predict(&[1., 2., 3.]).unwrap();
+
+    assert_eq!(output, vec![2., 4., 6.]);
+    assert_eq!(
+        *predictor.steps.lock().unwrap(),
+        vec!["preprocess", "run", "postprocess"]
+    );
+    assert_eq!(predictor.metadata().model_name, "mock");
+}
+
+#[test]
+fn predict_stops_on_error() {
+    let predictor = MockPredictor::new();
+
+    let output = predictor.predict(&[]);
+
+    assert!(matches!(output, Err(Error::InvalidInput(_))));
+    assert_eq!(*predictor.steps.lock().unwrap(), vec!["preprocess"]);
+}
@@ -0,0 +1,97 @@
+use ml_rust::{
+    config::{ModelData, ModelSet},
+    error::Error,
+    ml::registry::ModelRegistry,
+};
+
+fn model(name: &str, default: bool) -> ModelData {
+    ModelData {
+        default,
+        ..ModelData::new(format!("{name}.onnx"), name.to_string())
+    }
+}
+
+fn model_set(models: &[ModelData]) -> ModelSet {
+    ModelSet::Named(
+        models
+            .iter()
+            .map(|model| (model.model_name.clone(), model.clone()))
+            .collect(),
+    )
+}
+
+fn registry() -> ModelRegistry<String> {
+    let config = model_set(&[model("antelopev2", true), model("buffalo_l", false)]);
+
+    ModelRegistry::from_config("recognizer", &config, |model| Ok(model.model_path)).unwrap()
+}
+
+#[test]
+fn model_is_selected_by_name() {
+    let registry = registry();
+
+    assert_eq!(registry.get(Some("buffalo_l")).unwrap(), "buffalo_l.onnx");
+    assert_eq!(registry.get(None).unwrap(), "antelopev2.onnx");
+    assert_eq!(registry.default_name(), "antelopev2");
+}
+
+#[test]
+fn unknown_model_is_an_error() {
+    let registry = registry();
+
+    assert!(matches!(
+        registry.get(Some("glint360k")),
+        Err(Error::UnknownModel(name)) if name == "glint360k"
+    ));
+}
+
+#[test]
+fn single_model_is_named_after_model_name() {
+    let config = ModelSet::from(ModelData::new(
+        "recognizer.onnx".to_string(),
+        "recognizer".to_string(),
+    ));
+
+    let registry =
+        ModelRegistry::from_config("recognizer", &config, |model| Ok(model.model_path)).unwrap();
+
+    assert_eq!(registry.default_name(), "recognizer");
+    assert_eq!(registry.get(None).unwrap(), "recognizer.onnx");
+}
+
+#[test]
+fn reload_rebuilds_only_changed_models() {
+    let registry = registry();
+
+    let mut buffalo_l = model("buffalo_l", true);
+    buffalo_l.pool_size = 4;
+    let config = model_set(&[model("antelopev2", false), buffalo_l]);
+
+    let update = registry
+        .prepare("recognizer", &config, |model| {
+            Ok(format!("{} x{}", model.model_path, model.pool_size))
+        })
+        .unwrap();
+
+    // До применения обновления запросы получают старые модели
+    assert_eq!(registry.get(Some("buffalo_l")).unwrap(), "buffalo_l.onnx");
+
+    assert_eq!(registry.apply(update), ["buffalo_l"]);
+    assert_eq!(registry.default_name(), "buffalo_l");
+    assert_eq!(registry.get(None).unwrap(), "buffalo_l.onnx x4");
+    assert_eq!(registry.get(Some("antelopev2")).unwrap(), "antelopev2.onnx");
+}
+
+#[test]
+fn failed_reload_keeps_current_models() {
+    let registry = registry();
+
+    let config = model_set(&[model("antelopev2", true), model("glint360k", false)]);
+    let result = registry.prepare("recognizer", &config, |_| {
+        Err(Error::Config("broken model".to_string()))
+    });
+
+    assert!(result.is_err());
+    assert_eq!(registry.models().len(), 2);
+    assert_eq!(registry.get(Some("buffalo_l")).unwrap(), "buffalo_l.onnx");
+}
@@ -0,0 +1,259 @@
+use std::{io::Cursor, sync::Arc};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::DefaultBodyLimit,
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    Router,
+};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use ml_rust::{
+    config::{DetectionOptions, ModelData, ModelSet},
+    error::{Error, Result},
+    ml::{
+        inference_pool::InferencePool, registry::ModelRegistry, FaceDetection, FaceEmbedding,
+        ModelFingerprint, ModelMetadata, Predictor,
+    },
+    models::DetectedFaceOutput,
+    router::face::{routes, FaceState},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+const BOUNDARY: &str = "mock-boundary";
+
+fn metadata(name: &str) -> ModelMetadata {
+    ModelMetadata {
+        model_name: name.to_string(),
+        model_path: "".to_string(),
+        input_shape: vec![-1],
+        inputs: vec![],
+        outputs: vec![],
+        fingerprint: ModelFingerprint {
+            model_name: name.to_string(),
+            sha256: "".to_string(),
+        },
+    }
+}
+
+/// Детектор-заглушка: находит три лица в левом верхнем углу изображения
+/// с оценкой `score_threshold` и возвращает не больше `max_faces` из них.
+#[derive(Clone)]
+struct MockDetector {
+    metadata: ModelMetadata,
+    options: DetectionOptions,
+}
+
+impl Predictor for MockDetector {
+    type Input<'a> = &'a DynamicImage;
+    type Output = Vec<DetectedFaceOutput>;
+    type Tensor = (u32, u32);
+    type RawOutput = Vec<DetectedFaceOutput>;
+
+    fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    fn preprocess(&self, image: &DynamicImage) -> Result<(u32, u32)> {
+        Ok(image.dimensions())
+    }
+
+    fn run(&self, _: (u32, u32)) -> Result<Vec<DetectedFaceOutput>> {
+        Ok((0..3)
+            .map(|i| {
+                let x = i as f32;
+                DetectedFaceOutput::new(self.options.score_threshold, [x, x, x + 2., x + 2.], None)
+            })
+            .collect())
+    }
+
+    fn postprocess(
+        &self,
+        _: &DynamicImage,
+        faces: Vec<DetectedFaceOutput>,
+    ) -> Result<Vec<DetectedFaceOutput>> {
+        Ok(faces)
+    }
+}
+
+impl FaceDetection for MockDetector {
+    fn options(&self) -> &DetectionOptions {
+        &self.options
+    }
+
+    fn detect(
+        &self,
+        image: &DynamicImage,
+        options: &DetectionOptions,
+    ) -> Result<Vec<DetectedFaceOutput>> {
+        let detector = MockDetector {
+            options: options.clone(),
+            ..self.clone()
+        };
+        let mut faces = detector.predict(image)?;
+        faces.truncate(options.max_faces.unwrap_or(faces.len()));
+        Ok(faces)
+    }
+}
+
+/// Модель распознавания-заглушка: эмбеддинг лица — левый верхний угол рамки,
+/// лицо в начале координат выровнять невозможно.
+#[derive(Clone)]
+struct MockRecognizer {
+    metadata: ModelMetadata,
+}
+
+impl Predictor for MockRecognizer {
+    type Input<'a> = &'a [DetectedFaceOutput];
+    type Output = Vec<Vec<f32>>;
+    type Tensor = Vec<Vec<f32>>;
+    type RawOutput = Vec<Vec<f32>>;
+
+    fn metadata(&self) -> &ModelMetadata {
+        &self.metadata
+    }
+
+    fn preprocess(&self, faces: &[DetectedFaceOutput]) -> Result<Vec<Vec<f32>>> {
+        Ok(faces.iter().map(|face| face.bbox[..2].to_vec()).collect())
+    }
+
+    fn run(&self, crops: Vec<Vec<f32>>) -> Result<Vec<Vec<f32>>> {
+        Ok(crops)
+    }
+
+    fn postprocess(&self, _: &[DetectedFaceOutput], raw: Vec<Vec<f32>>) -> Result<Vec<Vec<f32>>> {
+        Ok(raw)
+    }
+}
+
+impl FaceEmbedding for MockRecognizer {
+    async fn embed_faces(
+        &self,
+        inference: &InferencePool,
+        _: DynamicImage,
+        faces: Vec<DetectedFaceOutput>,
+    ) -> Result<Vec<Result<Vec<f32>>>> {
+        let model = self.clone();
+        inference
+            .spawn(move || {
+                let aligned = |face: &DetectedFaceOutput| face.bbox[0] > 0.;
+                let crops: Vec<_> = faces.iter().filter(|face| aligned(face)).cloned().collect();
+                let mut embeddings = model.predict(&crops)?.into_iter();
+
+                Ok(faces
+                    .iter()
+                    .map(|face| match aligned(face) {
+                        true => Ok(embeddings.next().unwrap()),
+                        false => Err(Error::InvalidInput("face cannot be aligned".to_string())),
+                    })
+                    .collect())
+            })
+            .await
+    }
+}
+
+fn app() -> Router {
+    let detector = MockDetector {
+        metadata: metadata("mock-detector"),
+        options: DetectionOptions::default(),
+    };
+    let recognizer = MockRecognizer {
+        metadata: metadata("mock-recognizer"),
+    };
+    let config = |name: &str| ModelSet::from(ModelData::new(format!("{name}.onnx"), name.into()));
+
+    routes(FaceState {
+        detectors: Arc::new(
+            ModelRegistry::from_config("detector", &config("mock-detector"), |_| {
+                Ok(detector.clone())
+            })
+            .unwrap(),
+        ),
+        recognizers: Arc::new(
+            ModelRegistry::from_config("recognizer", &config("mock-recognizer"), |_| {
+                Ok(recognizer.clone())
+            })
+            .unwrap(),
+        ),
+        inference: InferencePool::new(Some(1)),
+    })
+}
+
+/// Запрос с формой, в поле `image` которой передано изображение 8×8 в PNG.
+fn image_request(uri: &str) -> Request<Body> {
+    let mut png = vec![];
+    DynamicImage::new_rgb8(8, 8)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .unwrap();
+
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"face.png\"\r\n\
+         Content-Type: image/png\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend(png);
+    body.extend(format!("\r\n--{BOUNDARY}--\r\n").into_bytes());
+
+    Request::post(uri)
+        .header(
+            CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn send(app: Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn detection_query_reaches_detector() {
+    let (status, faces) = send(
+        app(),
+        image_request("/detecting-faces?max_faces=2&score_threshold=0.7"),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let faces = faces.as_array().unwrap();
+    assert_eq!(faces.len(), 2);
+    assert!((faces[0]["score"].as_f64().unwrap() - 0.7).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn unaligned_face_gets_error_instead_of_embedding() {
+    let (status, output) = send(app(), image_request("/recognition-faces")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(output["model"]["model_name"], "mock-recognizer");
+
+    let faces = output["faces"].as_array().unwrap();
+    assert_eq!(faces.len(), 3);
+    assert!(faces[0]["embedding"].is_null());
+    assert!(faces[0]["error"].is_object());
+    assert_eq!(faces[1]["embedding"], serde_json::json!([1.0, 1.0]));
+    assert_eq!(faces[2]["embedding"], serde_json::json!([2.0, 2.0]));
+}
+
+#[tokio::test]
+async fn unknown_model_is_not_found() {
+    let (status, error) = send(app(), image_request("/detecting-faces?model=unknown")).await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["code"], "unknown_model");
+}
+
+#[tokio::test]
+async fn body_over_limit_is_payload_too_large() {
+    let app = app().layer(DefaultBodyLimit::max(16));
+
+    let (status, error) = send(app, image_request("/detecting-faces")).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(error["code"], "payload_too_large");
+}
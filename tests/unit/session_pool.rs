@@ -0,0 +1,54 @@
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use ml_rust::ml::session_pool::FifoPool;
+
+/// Ожидает, пока в очереди пула не окажется `depth` ожидающих.
+fn wait_for_queue(pool: &FifoPool<()>, depth: usize) {
+    let started = Instant::now();
+    while pool.queue_depth() != depth {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "queue depth is {}, expected {depth}",
+            pool.queue_depth()
+        );
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn waiters_are_served_in_queue_order() {
+    let pool = FifoPool::new(vec![()]);
+    let served = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        let guard = pool.acquire();
+
+        // Каждый следующий ожидающий встает в очередь после предыдущего
+        for waiter in 0..4 {
+            let (pool, served) = (&pool, &served);
+            scope.spawn(move || {
+                let _guard = pool.acquire();
+                served.lock().unwrap().push(waiter);
+            });
+            wait_for_queue(pool, waiter + 1);
+        }
+
+        let status = pool.status();
+        assert_eq!(
+            (status.pool_size, status.in_use, status.queue_depth),
+            (1, 1, 4)
+        );
+        assert!(served.lock().unwrap().is_empty());
+
+        drop(guard);
+    });
+
+    assert_eq!(*served.lock().unwrap(), vec![0, 1, 2, 3]);
+
+    let status = pool.status();
+    assert_eq!((status.in_use, status.queue_depth), (0, 0));
+}
@@ -0,0 +1,92 @@
+use ml_rust::{
+    config::TilingOptions,
+    ml::facial_processing::{tiles, Tile},
+    models::DetectedFaceOutput,
+};
+
+const OPTIONS: TilingOptions = TilingOptions {
+    tile_size: 640,
+    overlap: 128,
+};
+
+#[test]
+fn small_image_is_not_tiled() {
+    assert!(tiles((640, 480), &OPTIONS).is_empty());
+}
+
+#[test]
+fn tiles_overlap_and_cover_image() {
+    let tiles = tiles((1600, 700), &OPTIONS);
+
+    let columns: Vec<u32> = tiles
+        .iter()
+        .filter(|tile| tile.y == 0)
+        .map(|tile| tile.x)
+        .collect();
+    assert_eq!(columns, vec![0, 512, 960]);
+
+    let rows: Vec<u32> = tiles
+        .iter()
+        .filter(|tile| tile.x == 0)
+        .map(|tile| tile.y)
+        .collect();
+    assert_eq!(rows, vec![0, 60]);
+
+    assert!(tiles
+        .iter()
+        .all(|tile| tile.width == 640 && tile.height == 640));
+}
+
+#[test]
+fn tiles_of_narrow_image_keep_its_width() {
+    let tiles = tiles((300, 1000), &OPTIONS);
+
+    assert_eq!(
+        tiles,
+        vec![
+            Tile {
+                x: 0,
+                y: 0,
+                width: 300,
+                height: 640
+            },
+            Tile {
+                x: 0,
+                y: 360,
+                width: 300,
+                height: 640
+            },
+        ]
+    );
+}
+
+#[test]
+fn faces_cut_by_inner_edges_are_detected() {
+    let tile = Tile {
+        x: 512,
+        y: 0,
+        width: 640,
+        height: 640,
+    };
+
+    // Левая граница фрагмента внутри изображения, верхняя совпадает с краем изображения
+    assert!(tile.cuts(&[0., 100., 50., 150.], (1600, 700)));
+    assert!(!tile.cuts(&[100., 0., 150., 50.], (1600, 700)));
+    assert!(tile.cuts(&[600., 600., 639.5, 639.5], (1600, 700)));
+    assert!(!tile.cuts(&[100., 100., 150., 150.], (1600, 700)));
+}
+
+#[test]
+fn tile_faces_are_moved_to_image_coordinates() {
+    let tile = Tile {
+        x: 512,
+        y: 60,
+        width: 640,
+        height: 640,
+    };
+    let face = DetectedFaceOutput::new(0.9, [10., 20., 30., 40.], Some([(15., 25.); 5]));
+
+    let face = tile.to_image(face);
+    assert_eq!(face.bbox, [522., 80., 542., 100.]);
+    assert_eq!(face.landmarks, Some([(527., 85.); 5]));
+}
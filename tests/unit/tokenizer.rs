@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+use ml_rust::{error::Error, ml::search::pad_token_id};
+use tokenizers::Tokenizer;
+
+/// Токенизатор WordLevel со словарем `vocab` и настройками дополнения `padding`.
+fn tokenizer(vocab: &str, padding: &str) -> Tokenizer {
+    Tokenizer::from_str(&format!(
+        r#"{{
+            "version": "1.0",
+            "truncation": null,
+            "padding": {padding},
+            "added_tokens": [],
+            "normalizer": null,
+            "pre_tokenizer": null,
+            "post_processor": null,
+            "decoder": null,
+            "model": {{ "type": "WordLevel", "vocab": {vocab}, "unk_token": "<unk>" }}
+        }}"#
+    ))
+    .unwrap()
+}
+
+#[test]
+fn pad_id_is_taken_from_padding_settings_or_vocabulary() {
+    let padding = r#"{
+        "strategy": "BatchLongest", "direction": "Right", "pad_to_multiple_of": null,
+        "pad_id": 3, "pad_type_id": 0, "pad_token": "<pad>"
+    }"#;
+    let vocab = r#"{ "<unk>": 0, "hello": 1, "<|endoftext|>": 2, "<pad>": 3 }"#;
+    assert_eq!(pad_token_id(&tokenizer(vocab, padding)).unwrap(), 3);
+
+    // CLIP BPE: без настроек дополнения дополнение токеном конца текста, а не нулем
+    let vocab = r#"{ "<unk>": 0, "hello": 1, "<|endoftext|>": 2 }"#;
+    assert_eq!(pad_token_id(&tokenizer(vocab, "null")).unwrap(), 2);
+}
+
+#[test]
+fn tokenizer_without_pad_token_is_rejected() {
+    let vocab = r#"{ "<unk>": 0, "hello": 1 }"#;
+
+    assert!(matches!(
+        pad_token_id(&tokenizer(vocab, "null")),
+        Err(Error::Config(_))
+    ));
+}
@@ -1,4 +1,9 @@
-use ml_rust::ml::facial_processing::umeyama;
+use image::{DynamicImage, GenericImageView, Rgba, Rgba32FImage};
+use ml_rust::{
+    error::Error,
+    ml::facial_processing::{aligned_face_tensor, resize, umeyama, Letterbox},
+    models::{DetectedFaceOutput, RecognizedFaceOutput},
+};
 
 const SRC: [(f32, f32); 5] = [
     (491.7426, 321.8467),
@@ -18,7 +23,7 @@ const DST: [(f32, f32); 5] = [
 
 #[test]
 fn estimate() {
-    let result = umeyama(&SRC, &DST);
+    let result = umeyama(&SRC, &DST).unwrap();
 
     const R: nalgebra::Matrix<
         f32,
@@ -39,3 +44,67 @@ fn estimate() {
 
     assert_eq!(result, R);
 }
+
+#[test]
+fn estimate_degenerate_landmarks() {
+    let src = [(100f32, 100f32); 5];
+
+    assert!(umeyama(&src, &DST).is_err());
+}
+
+#[test]
+fn letterbox_maps_square_image_back() {
+    let letterbox = Letterbox::new((1000, 1000), (640, 640), false);
+
+    assert_eq!(letterbox.scale, (0.64, 0.64));
+    assert_eq!(letterbox.padding, (0., 0.));
+    assert_eq!(letterbox.inverse((640., 320.)), (1000., 500.));
+}
+
+#[test]
+fn letterbox_inverse_undoes_forward() {
+    let letterbox = Letterbox::new((1000, 500), (640, 640), true);
+
+    assert_eq!(letterbox.resized_size(), (640, 320));
+    assert_eq!(letterbox.padding, (0., 160.));
+
+    let point = (123.5, 456.25);
+    let (x, y) = letterbox.inverse(letterbox.forward(point));
+    assert!((x - point.0).abs() < 1e-3);
+    assert!((y - point.1).abs() < 1e-3);
+}
+
+#[test]
+fn resize_pads_with_black() {
+    let image = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+        100,
+        50,
+        image::Rgb([255, 255, 255]),
+    ));
+
+    let (resized, letterbox) = resize(&image, 64, 64, true);
+
+    assert_eq!(resized.dimensions(), (64, 64));
+    assert_eq!(letterbox.padding, (0., 16.));
+    assert_eq!(resized.get_pixel(32, 8), Rgba([0, 0, 0, 255]));
+    assert_eq!(resized.get_pixel(32, 32), Rgba([255, 255, 255, 255]));
+    assert_eq!(resized.get_pixel(32, 56), Rgba([0, 0, 0, 255]));
+}
+
+#[test]
+fn face_without_landmarks_is_reported_individually() {
+    let image = Rgba32FImage::new(640, 480);
+    let aligned = DetectedFaceOutput::new(0.9, [480., 310., 545., 390.], Some(SRC));
+    let unaligned = DetectedFaceOutput::new(0.8, [100., 100., 160., 180.], None);
+
+    let tensor = aligned_face_tensor(&image, &aligned, 112).unwrap();
+    assert_eq!(tensor.shape(), &[1, 3, 112, 112]);
+
+    let err = aligned_face_tensor(&image, &unaligned, 112).unwrap_err();
+    assert!(matches!(err, Error::FaceAlignment(_)));
+
+    let output = RecognizedFaceOutput::from_error(&unaligned, &err);
+    assert_eq!(output.embedding, None);
+    assert_eq!(output.error.unwrap().code, "face_alignment");
+    assert_eq!(output.bbox, unaligned.bbox);
+}
@@ -0,0 +1,118 @@
+use ml_rust::{
+    config::ModelData,
+    error::Error,
+    ml::{
+        validation::{
+            batch_limit, check_batch_dimension, check_count, check_model_files, check_tensor,
+            ValidationReport,
+        },
+        TensorInfo,
+    },
+};
+
+fn tensor(name: &str, shape: &[i64]) -> TensorInfo {
+    TensorInfo {
+        name: name.to_string(),
+        shape: shape.to_vec(),
+    }
+}
+
+#[test]
+fn dynamic_dimensions_match_any_size() {
+    let inputs = [tensor("input.1", &[-1, 3, -1, -1])];
+
+    assert_eq!(check_tensor("input", &inputs, 0, &[1, 3, 640, 640]), None);
+    assert_eq!(check_tensor("input", &inputs, 0, &[-1, 3, 112, 112]), None);
+}
+
+#[test]
+fn shape_mismatch_is_reported() {
+    let outputs = [tensor("embedding", &[1, 256])];
+
+    let problem = check_tensor("output", &outputs, 0, &[-1, 512]).unwrap();
+    assert!(problem.contains("embedding"));
+    assert!(problem.contains("[1, 256]"));
+
+    assert!(check_tensor("output", &outputs, 0, &[-1, 512, 1]).is_some());
+}
+
+#[test]
+fn missing_tensor_and_wrong_count_are_reported() {
+    let outputs = [tensor("last_hidden_state", &[-1, -1, 768])];
+
+    let problem = check_tensor("output", &outputs, 1, &[-1, -1]).unwrap();
+    assert!(problem.contains("output #1 is missing"));
+
+    assert_eq!(check_count("output", &outputs, 1), None);
+    assert_eq!(
+        check_count("output", &outputs, 9).unwrap(),
+        "expected 9 outputs, got 1"
+    );
+}
+
+#[test]
+fn report_fails_with_all_problems() {
+    let mut report = ValidationReport::default();
+    report.add("detector", "detector.onnx", vec![]);
+    report.add(
+        "recognizer",
+        "recognizer.onnx",
+        vec!["output #0 `683` has shape [1, 256], expected [-1, 512]".to_string()],
+    );
+
+    assert!(!report.is_ok());
+
+    let text = report.to_string();
+    assert!(text.contains("[ok] detector (detector.onnx)"));
+    assert!(text.contains("[error] recognizer (recognizer.onnx)"));
+    assert!(text.contains("expected [-1, 512]"));
+
+    assert!(matches!(
+        report.into_result(),
+        Err(Error::ModelValidation(_))
+    ));
+}
+
+#[test]
+fn missing_model_files_are_reported() {
+    let present = ModelData::new("Cargo.toml".to_string(), "present".to_string());
+    let missing = ModelData::new("missing/model.onnx".to_string(), "missing".to_string());
+
+    let report = check_model_files([("present", &present), ("missing", &missing)]);
+
+    assert!(report.models[0].problems.is_empty());
+    assert_eq!(report.models[1].problems.len(), 1);
+    assert!(!report.is_ok());
+}
+
+#[test]
+fn fixed_batch_model_limits_batch_size() {
+    let fixed = [1, 3, 224, 224];
+    assert_eq!(batch_limit(&fixed, 16), 1);
+    assert_eq!(check_batch_dimension(&fixed), None);
+
+    let dynamic = [-1, 3, 224, 224];
+    assert_eq!(batch_limit(&dynamic, 16), 16);
+    assert_eq!(batch_limit(&dynamic, 0), 1);
+    assert_eq!(check_batch_dimension(&dynamic), None);
+}
+
+#[test]
+fn fixed_batch_larger_than_one_is_reported() {
+    let input_shape = [4, 3, 112, 112];
+
+    assert_eq!(batch_limit(&input_shape, 16), 4);
+    let problem = check_batch_dimension(&input_shape).unwrap();
+    assert!(problem.contains("fixed to 4"));
+}
+
+#[test]
+fn fixed_batch_text_model_limits_batch_size() {
+    // input_ids текстовой модели, экспортированной с батчем 1 и динамической длиной
+    let input_ids = [1, -1];
+    assert_eq!(batch_limit(&input_ids, 32), 1);
+    assert_eq!(check_batch_dimension(&input_ids), None);
+
+    let problem = check_batch_dimension(&[8, 77]).unwrap();
+    assert!(problem.contains("fixed to 8"));
+}